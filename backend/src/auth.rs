@@ -0,0 +1,75 @@
+use crate::models::user::Role;
+use crate::repositories::Repository;
+use crate::utils::jwt::_verify_jwt;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorInternalServerError, ErrorUnauthorized};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use std::sync::Arc;
+
+// The user a request's bearer token belongs to. Handlers take it as an
+// argument; extraction fails with 401 when the token is missing, invalid or
+// revoked.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub user_id: String,
+    pub role: Role,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        // Already verified by `require_auth` for this request.
+        if let Some(user) = req.extensions().get::<AuthenticatedUser>() {
+            let user = user.clone();
+            return Box::pin(async move { Ok(user) });
+        }
+
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+        let repo = req.app_data::<web::Data<Arc<dyn Repository>>>().cloned();
+
+        Box::pin(async move {
+            let (Some(token), Some(repo)) = (token, repo) else {
+                return Err(ErrorUnauthorized("Invalid token"));
+            };
+            authenticate(&token, repo.get_ref().as_ref()).await
+        })
+    }
+}
+
+// Verifies an access token and loads its user's role. Shared with
+// connections that carry the token outside the `Authorization` header.
+pub async fn authenticate(token: &str, repo: &dyn Repository) -> Result<AuthenticatedUser, Error> {
+    let user_id = match _verify_jwt(token, repo).await {
+        Ok(user_id) => user_id,
+        Err(_) => return Err(ErrorUnauthorized("Invalid token")),
+    };
+
+    let role = match repo.find_user_by_id(&user_id).await {
+        Ok(user) => user.map(|user| user.role).unwrap_or_default(),
+        Err(_) => return Err(ErrorInternalServerError("Failed to load user")),
+    };
+
+    Ok(AuthenticatedUser { user_id, role })
+}
+
+// Route guard rejecting unauthenticated requests before the handler runs.
+// Use with `actix_web::middleware::from_fn(require_auth)`.
+pub async fn require_auth(
+    user: AuthenticatedUser,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    req.extensions_mut().insert(user);
+    next.call(req).await
+}
@@ -1,4 +1,8 @@
 pub mod user;
 pub mod poll;
 pub mod vote;
-pub mod websocket;
\ No newline at end of file
+pub mod websocket;
+pub mod sse;
+
+#[cfg(test)]
+mod test_support;
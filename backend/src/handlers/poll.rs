@@ -1,9 +1,16 @@
-use crate::models::poll::Poll;
+use crate::models::poll::{Poll, PollRole, PublicPoll, ScoreRange, VotingMethod};
+use crate::auth::AuthenticatedUser;
+use crate::policy::{self, Action, PollAction};
+use crate::realtime::registry;
+use crate::reconciliation;
+use crate::repositories::poll_query::{PollCursor, PollQuery, PollSort, PollStatusFilter};
 use crate::repositories::Repository;
+use crate::tally;
 use actix_web::{web, HttpResponse, Responder};
-use actix_web_httpauth::extractors::bearer::BearerAuth;
+use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use super::websocket::{broadcast_poll_update, PollUpdate};
@@ -12,21 +19,58 @@ use super::websocket::{broadcast_poll_update, PollUpdate};
 pub struct CreatePollData {
     pub question: String,
     pub options: Vec<String>,
-    pub created_by: String,
     pub is_multiple_choice: bool,
+    #[serde(default)]
+    pub voting_method: VotingMethod,
+    // Only used by score polls; defaults to 0-5.
+    pub score_range: Option<ScoreRange>,
+    pub opens_at: Option<DateTime<Utc>>,
+    pub closes_at: Option<DateTime<Utc>>,
 }
 
 // Create Poll Handler
 pub async fn create_poll(
     repo: web::Data<Arc<dyn Repository>>,
     web::Json(poll_data): web::Json<CreatePollData>,
+    user: AuthenticatedUser,
 ) -> impl Responder {
+    if !policy::can(&user, Action::CreatePoll) {
+        return policy::forbidden("You are not allowed to create polls.");
+    }
+
+    let score_range = match poll_data.voting_method {
+        VotingMethod::Score => {
+            let range = poll_data.score_range.unwrap_or_default();
+            if !range.is_valid() {
+                return HttpResponse::BadRequest().body(format!(
+                    "Score range minimum must be below its maximum and at most {} apart",
+                    ScoreRange::MAX_SPAN
+                ));
+            }
+            Some(range)
+        }
+        _ => None,
+    };
+
+    if let (Some(opens_at), Some(closes_at)) = (poll_data.opens_at, poll_data.closes_at) {
+        if closes_at <= opens_at {
+            return HttpResponse::BadRequest().body("Poll must close after it opens");
+        }
+    }
+
+    if poll_data.closes_at.is_some_and(|closes_at| closes_at <= Utc::now()) {
+        return HttpResponse::BadRequest().body("Poll closing time must be in the future");
+    }
+
     let new_poll = Poll::_new(
         poll_data.question,
         poll_data.options,
-        poll_data.created_by,
+        user.user_id,
         poll_data.is_multiple_choice,
-    );
+        poll_data.voting_method,
+        score_range,
+    )
+    .with_schedule(poll_data.opens_at, poll_data.closes_at);
 
     match repo.create_poll(new_poll).await {
         Ok(_) => HttpResponse::Ok().body("Poll created successfully"),
@@ -34,34 +78,137 @@ pub async fn create_poll(
     }
 }
 
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 100;
+
+// Query parameters shared by the poll listings. The next page's `after` is
+// returned in the `X-Next-Cursor` header.
+#[derive(Deserialize)]
+pub struct PollListParams {
+    pub limit: Option<usize>,
+    pub after: Option<String>,
+    pub status: Option<PollStatusFilter>,
+    pub created_by: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    // Filters on whether the caller has voted; requires authentication.
+    pub has_voted: Option<bool>,
+    #[serde(default)]
+    pub sort: PollSort,
+}
+
+fn poll_query(
+    params: PollListParams,
+    user: Option<AuthenticatedUser>,
+) -> Result<PollQuery, HttpResponse> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        return Err(HttpResponse::BadRequest()
+            .body(format!("Limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+
+    let after = match params.after {
+        Some(after) => match PollCursor::decode(&after) {
+            Some(cursor) if cursor.sort() == params.sort => Some(cursor),
+            _ => return Err(HttpResponse::BadRequest().body("Invalid cursor for this sort order")),
+        },
+        None => None,
+    };
+
+    let voted_by = match (params.has_voted, user) {
+        (Some(has_voted), Some(user)) => Some((user.user_id, has_voted)),
+        (Some(_), None) => {
+            return Err(HttpResponse::Unauthorized().body("Log in to filter by your votes"))
+        }
+        (None, _) => None,
+    };
+
+    Ok(PollQuery {
+        limit,
+        after,
+        status: params.status,
+        created_by: params.created_by,
+        created_after: params.created_after,
+        created_before: params.created_before,
+        voted_by,
+        sort: params.sort,
+    })
+}
+
+fn page_response<T: Serialize>(items: Vec<T>, next_cursor: Option<PollCursor>) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    if let Some(cursor) = next_cursor {
+        response.insert_header(("X-Next-Cursor", cursor.encode()));
+    }
+    response.json(items)
+}
+
 // Get All Polls Summary Handler
 pub async fn get_all_polls_summary(
     repo: web::Data<Arc<dyn Repository>>,
+    params: web::Query<PollListParams>,
+    user: Option<AuthenticatedUser>,
 ) -> impl Responder {
-    match repo.get_all_polls().await {
-        Ok(polls) => {
-            let mut poll_summaries = Vec::new();
-
-            for poll in polls {
-                let user_name = match repo.find_user_by_id(&poll.created_by).await {
-                    Ok(Some(user)) => user.name,
-                    Ok(None) => "Unknown".to_string(),
-                    Err(_) => "Unknown".to_string(),
-                };
-
-                poll_summaries.push(serde_json::json!({
-                    "id": poll.id.unwrap().to_hex(),
-                    "question": poll.question,
-                    "created_by": user_name,
-                    "created_at": poll.created_at.to_rfc3339(),
-                    "isactive": poll.isactive,
-                }));
-            }
+    let caller = user.as_ref().map(|user| user.user_id.clone());
+    let query = match poll_query(params.into_inner(), user) {
+        Ok(query) => query,
+        Err(response) => return response,
+    };
+
+    let page = match repo.list_polls(&query).await {
+        Ok(page) => page,
+        Err(_) => return HttpResponse::InternalServerError().body("Failed to fetch polls"),
+    };
+
+    // Creators and the caller's votes are each fetched for the whole page at
+    // once; vote counts come with the page.
+    let poll_ids: Vec<ObjectId> = page.polls.iter().filter_map(|poll| poll.id).collect();
+    let creator_ids: Vec<String> = page
+        .polls
+        .iter()
+        .map(|poll| poll.created_by.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
 
-            HttpResponse::Ok().json(poll_summaries)
+    // Creators that can't be loaded are shown as "Unknown" below.
+    let creators: HashMap<String, String> = match repo.find_users_by_ids(creator_ids).await {
+        Ok(users) => users.into_iter().map(|user| (user.user_id, user.name)).collect(),
+        Err(e) => {
+            log::warn!("Failed to load poll creators: {}", e);
+            HashMap::new()
         }
-        Err(_) => HttpResponse::InternalServerError().body("Failed to fetch polls"),
-    }
+    };
+    let voted: Option<HashSet<ObjectId>> = match &caller {
+        Some(user_id) => match repo.find_voted_poll_ids(user_id, poll_ids).await {
+            Ok(ids) => Some(ids.into_iter().collect()),
+            Err(_) => return HttpResponse::InternalServerError().body("Failed to fetch polls"),
+        },
+        None => None,
+    };
+
+    let poll_summaries: Vec<_> = page
+        .polls
+        .into_iter()
+        .map(|poll| {
+            let id = poll.id.unwrap();
+            serde_json::json!({
+                "id": id.to_hex(),
+                "question": poll.question,
+                "created_by": creators
+                    .get(&poll.created_by)
+                    .map(String::as_str)
+                    .unwrap_or("Unknown"),
+                "created_at": poll.created_at.to_rfc3339(),
+                "isactive": poll.isactive,
+                "vote_count": page.ballot_counts.get(&id).copied().unwrap_or(0),
+                // Null for anonymous callers.
+                "has_voted": voted.as_ref().map(|voted| voted.contains(&id)),
+            })
+        })
+        .collect();
+
+    page_response(poll_summaries, page.next_cursor)
 }
 
 
@@ -70,13 +217,13 @@ pub async fn get_poll_by_id(
     repo: web::Data<Arc<dyn Repository>>,
     poll_id: web::Path<String>,
 ) -> impl Responder {
-    let object_id = match ObjectId::parse_str(&poll_id.into_inner()) {
+    let object_id = match ObjectId::parse_str(poll_id.into_inner()) {
         Ok(id) => id,
         Err(_) => return HttpResponse::BadRequest().body("Invalid poll ID format"),
     };
 
     match repo.get_poll_by_id(object_id).await {
-        Ok(Some(poll)) => HttpResponse::Ok().json(poll),
+        Ok(Some(poll)) => HttpResponse::Ok().json(PublicPoll::from(poll)),
         Ok(None) => HttpResponse::NotFound().body("Poll not found"),
         Err(_) => HttpResponse::InternalServerError().body("Failed to retrieve poll"),
     }
@@ -86,9 +233,20 @@ pub async fn get_poll_by_id(
 pub async fn get_polls_by_user(
     repo: web::Data<Arc<dyn Repository>>,
     user_id: web::Path<String>,
+    params: web::Query<PollListParams>,
+    user: Option<AuthenticatedUser>,
 ) -> impl Responder {
-    match repo.get_polls_by_user(user_id.as_str()).await {
-        Ok(polls) => HttpResponse::Ok().json(polls),
+    let mut query = match poll_query(params.into_inner(), user) {
+        Ok(query) => query,
+        Err(response) => return response,
+    };
+    query.created_by = Some(user_id.into_inner());
+
+    match repo.list_polls(&query).await {
+        Ok(page) => {
+            let polls: Vec<PublicPoll> = page.polls.into_iter().map(PublicPoll::from).collect();
+            page_response(polls, page.next_cursor)
+        }
         Err(_) => HttpResponse::InternalServerError().body("Failed to retrieve polls"),
     }
 }
@@ -103,12 +261,8 @@ pub async fn toggle_poll_status(
     repo: web::Data<Arc<dyn Repository>>,
     poll_id: web::Path<String>,
     body: web::Json<ToggleStatusRequest>,
-    credentials: BearerAuth,
+    user: AuthenticatedUser,
 ) -> impl Responder {
-    let user_id = match crate::utils::jwt::_verify_jwt(credentials.token()) {
-        Ok(user_id) => user_id,
-        Err(_) => return HttpResponse::Unauthorized().body("Invalid token"),
-    };
     let poll_id_str = poll_id.to_string();
 
     let poll_object_id = match ObjectId::parse_str(&poll_id_str) {
@@ -118,7 +272,7 @@ pub async fn toggle_poll_status(
 
 
     match repo.get_poll_by_id(poll_object_id).await {
-        Ok(Some(poll)) if poll.created_by == user_id => {
+        Ok(Some(poll)) if policy::can_on_poll(&user, &poll, PollAction::UpdateStatus) => {
             match repo.update_poll_status(poll_object_id, body.isactive).await {
                 Ok(_) => {
                     broadcast_poll_update(PollUpdate::StatusUpdate {
@@ -131,14 +285,60 @@ pub async fn toggle_poll_status(
                 Err(_) => HttpResponse::InternalServerError().body("Failed to update poll status"),
             }
         }
-        Ok(Some(_)) => HttpResponse::Unauthorized().json(serde_json::json!({
-            "message": "You are not authorized to change the status of this poll."
-        })),
+        Ok(Some(_)) => policy::forbidden("You are not authorized to change the status of this poll."),
         Ok(None) => HttpResponse::NotFound().body("Poll not found"),
         Err(_) => HttpResponse::InternalServerError().body("Failed to retrieve poll"),
     }
 }
 
+#[derive(Deserialize)]
+pub struct CollaboratorRequest {
+    pub user_id: String,
+    // `None` removes the collaborator.
+    pub role: Option<PollRole>,
+}
+
+// Update Poll Collaborator Handler
+pub async fn update_collaborator(
+    repo: web::Data<Arc<dyn Repository>>,
+    poll_id: web::Path<String>,
+    body: web::Json<CollaboratorRequest>,
+    user: AuthenticatedUser,
+) -> impl Responder {
+    let poll_object_id = match ObjectId::parse_str(poll_id.into_inner()) {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid poll ID"),
+    };
+
+    let poll = match repo.get_poll_by_id(poll_object_id).await {
+        Ok(Some(poll)) => poll,
+        Ok(None) => return HttpResponse::NotFound().body("Poll not found"),
+        Err(_) => return HttpResponse::InternalServerError().body("Failed to retrieve poll"),
+    };
+
+    if !policy::can_on_poll(&user, &poll, PollAction::ManageCollaborators) {
+        return policy::forbidden("You are not authorized to manage collaborators of this poll.");
+    }
+
+    if body.user_id == poll.created_by {
+        return HttpResponse::BadRequest().body("The poll creator is always an owner");
+    }
+
+    let mut collaborators: Vec<(String, PollRole)> = poll
+        .collaborators
+        .into_iter()
+        .filter(|(collaborator, _)| *collaborator != body.user_id)
+        .collect();
+    if let Some(role) = body.role {
+        collaborators.push((body.user_id.clone(), role));
+    }
+
+    match repo.update_poll_collaborators(poll_object_id, collaborators.clone()).await {
+        Ok(_) => HttpResponse::Ok().json(collaborators),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to update collaborators"),
+    }
+}
+
 // Get Poll Results Handler
 #[derive(Serialize)]
 struct PollResult {
@@ -150,7 +350,7 @@ pub async fn get_poll_results(
     repo: web::Data<Arc<dyn Repository>>,
     poll_id: web::Path<String>,
 ) -> impl Responder {
-    let poll_object_id = match ObjectId::parse_str(&poll_id.into_inner()) {
+    let poll_object_id = match ObjectId::parse_str(poll_id.into_inner()) {
         Ok(id) => id,
         Err(_) => return HttpResponse::BadRequest().body("Invalid poll ID format"),
     };
@@ -170,3 +370,366 @@ pub async fn get_poll_results(
         Err(_) => HttpResponse::InternalServerError().body("Failed to retrieve poll results"),
     }
 }
+
+// Get Tally Drift Handler
+pub async fn get_tally_drift(
+    repo: web::Data<Arc<dyn Repository>>,
+    user: AuthenticatedUser,
+) -> impl Responder {
+    if !policy::can(&user, Action::ReconcileTallies) {
+        return policy::forbidden("Only admins can reconcile tallies.");
+    }
+
+    match reconciliation::reconcile(repo.get_ref().as_ref(), false, None).await {
+        Ok(drift) => HttpResponse::Ok().json(drift),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to reconcile tallies"),
+    }
+}
+
+// Repair Tally Drift Handler
+pub async fn repair_tally_drift(
+    repo: web::Data<Arc<dyn Repository>>,
+    user: AuthenticatedUser,
+) -> impl Responder {
+    if !policy::can(&user, Action::ReconcileTallies) {
+        return policy::forbidden("Only admins can reconcile tallies.");
+    }
+
+    if reconciliation::start_repair(repo.get_ref().clone()).await {
+        HttpResponse::Accepted().body("Tally repair started")
+    } else {
+        HttpResponse::Conflict().body("Tallies are already being reconciled; try again later")
+    }
+}
+
+// Get Poll Viewers Handler
+pub async fn get_poll_viewers(
+    repo: web::Data<Arc<dyn Repository>>,
+    poll_id: web::Path<String>,
+    user: AuthenticatedUser,
+) -> impl Responder {
+    let poll_object_id = match ObjectId::parse_str(poll_id.into_inner()) {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid poll ID format"),
+    };
+
+    match repo.get_poll_by_id(poll_object_id).await {
+        Ok(Some(poll)) if policy::can_on_poll(&user, &poll, PollAction::ViewViewers) => {
+            let poll_id = poll_object_id.to_hex();
+            let viewers = registry::viewers(&poll_id).await;
+            HttpResponse::Ok().json(serde_json::json!({
+                "poll_id": poll_id,
+                "viewers": viewers,
+            }))
+        }
+        Ok(Some(_)) => policy::forbidden("Only collaborators can see this poll's viewers."),
+        Ok(None) => HttpResponse::NotFound().body("Poll not found"),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to retrieve poll"),
+    }
+}
+
+// Loads a ranked-choice poll's options and ballots for the ranked tallies
+async fn load_ranked_ballots(
+    repo: &web::Data<Arc<dyn Repository>>,
+    poll_id: String,
+) -> Result<(Vec<ObjectId>, Vec<Vec<ObjectId>>), HttpResponse> {
+    let poll_object_id = match ObjectId::parse_str(poll_id) {
+        Ok(id) => id,
+        Err(_) => return Err(HttpResponse::BadRequest().body("Invalid poll ID format")),
+    };
+
+    let poll = match repo.get_poll_by_id(poll_object_id).await {
+        Ok(Some(poll)) => poll,
+        Ok(None) => return Err(HttpResponse::NotFound().body("Poll not found")),
+        Err(_) => return Err(HttpResponse::InternalServerError().body("Failed to retrieve poll")),
+    };
+
+    if poll.voting_method != VotingMethod::RankedChoice {
+        return Err(HttpResponse::BadRequest().json(serde_json::json!({
+            "message": "Ranked results are only available for ranked-choice polls."
+        })));
+    }
+
+    match repo.find_votes_by_poll(poll_object_id).await {
+        Ok(votes) => Ok((
+            poll.options.iter().map(|(id, _)| *id).collect(),
+            votes.into_iter().map(|vote| vote.option_ids).collect(),
+        )),
+        Err(_) => Err(HttpResponse::InternalServerError().body("Failed to retrieve poll results")),
+    }
+}
+
+// Get Instant-Runoff Results Handler
+pub async fn get_irv_results(
+    repo: web::Data<Arc<dyn Repository>>,
+    poll_id: web::Path<String>,
+) -> impl Responder {
+    match load_ranked_ballots(&repo, poll_id.into_inner()).await {
+        Ok((candidates, ballots)) => {
+            HttpResponse::Ok().json(tally::irv::tally(&candidates, &ballots))
+        }
+        Err(response) => response,
+    }
+}
+
+// Get Schulze Results Handler
+pub async fn get_schulze_results(
+    repo: web::Data<Arc<dyn Repository>>,
+    poll_id: web::Path<String>,
+) -> impl Responder {
+    match load_ranked_ballots(&repo, poll_id.into_inner()).await {
+        Ok((candidates, ballots)) => {
+            HttpResponse::Ok().json(tally::schulze::tally(&candidates, &ballots))
+        }
+        Err(response) => response,
+    }
+}
+
+// Get Score Results Handler
+pub async fn get_score_results(
+    repo: web::Data<Arc<dyn Repository>>,
+    poll_id: web::Path<String>,
+) -> impl Responder {
+    let poll_object_id = match ObjectId::parse_str(poll_id.into_inner()) {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid poll ID format"),
+    };
+
+    let poll = match repo.get_poll_by_id(poll_object_id).await {
+        Ok(Some(poll)) => poll,
+        Ok(None) => return HttpResponse::NotFound().body("Poll not found"),
+        Err(_) => return HttpResponse::InternalServerError().body("Failed to retrieve poll"),
+    };
+
+    if poll.voting_method != VotingMethod::Score {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "message": "Score results are only available for score polls."
+        }));
+    }
+
+    match repo.find_votes_by_poll(poll_object_id).await {
+        Ok(votes) => {
+            let candidates: Vec<ObjectId> = poll.options.iter().map(|(id, _)| *id).collect();
+            let ballots: Vec<Vec<(ObjectId, i32)>> =
+                votes.into_iter().map(|vote| vote.scores).collect();
+            let range = poll.score_range.unwrap_or_default();
+
+            HttpResponse::Ok().json(tally::score::tally(&candidates, range, &ballots))
+        }
+        Err(_) => HttpResponse::InternalServerError().body("Failed to retrieve poll results"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::test_support::{bearer, repo, seed_poll, sign_up};
+    use crate::models::user::Role;
+    use crate::models::vote::Vote;
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn creates_polls_for_members_only() {
+        let repo = repo();
+        let member = sign_up(&repo, "alice", Role::Member).await;
+        let viewer = sign_up(&repo, "bob", Role::Viewer).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/create_polls", web::post().to(create_poll)),
+        )
+        .await;
+        let body = serde_json::json!({
+            "question": "Lunch?",
+            "options": ["Pizza", "Soup"],
+            "is_multiple_choice": false,
+        });
+
+        let request = test::TestRequest::post()
+            .uri("/api/create_polls")
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 401);
+
+        let request = test::TestRequest::post()
+            .uri("/api/create_polls")
+            .insert_header(bearer(&viewer))
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 403);
+
+        let request = test::TestRequest::post()
+            .uri("/api/create_polls")
+            .insert_header(bearer(&member))
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 200);
+
+        let query = PollQuery {
+            limit: 10,
+            ..PollQuery::default()
+        };
+        let page = repo.list_polls(&query).await.unwrap();
+        assert_eq!(page.polls.len(), 1);
+        assert_eq!(page.polls[0].created_by, "alice");
+    }
+
+    #[actix_web::test]
+    async fn summaries_page_through_the_cursor() {
+        let repo = repo();
+        sign_up(&repo, "alice", Role::Member).await;
+        for question in ["first", "second", "third"] {
+            seed_poll(&repo, "alice", question).await.id.unwrap();
+        }
+        let app = test::init_service(App::new().app_data(repo.clone()).route(
+            "/api/all_polls_summary",
+            web::get().to(get_all_polls_summary),
+        ))
+        .await;
+
+        let mut questions = Vec::new();
+        let mut uri = "/api/all_polls_summary?limit=2".to_string();
+        loop {
+            let response =
+                test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+            assert_eq!(response.status(), 200);
+            let cursor = response
+                .headers()
+                .get("X-Next-Cursor")
+                .map(|cursor| cursor.to_str().unwrap().to_string());
+            let page: Vec<serde_json::Value> = test::read_body_json(response).await;
+            for summary in &page {
+                assert_eq!(summary["created_by"], "alice");
+                assert_eq!(summary["has_voted"], serde_json::Value::Null);
+                questions.push(summary["question"].as_str().unwrap().to_string());
+            }
+            match cursor {
+                Some(cursor) => uri = format!("/api/all_polls_summary?limit=2&after={}", cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(questions, vec!["third", "second", "first"]);
+    }
+
+    #[actix_web::test]
+    async fn summaries_count_ballots_and_the_callers_votes() {
+        let repo = repo();
+        let alice = sign_up(&repo, "alice", Role::Member).await;
+        let voted = seed_poll(&repo, "alice", "voted").await;
+        seed_poll(&repo, "alice", "unvoted").await;
+        for user_id in ["alice", "bob"] {
+            repo.submit_or_update_vote(Vote::_new(
+                voted.id.unwrap(),
+                vec![voted.options[0].0],
+                user_id.to_string(),
+            ))
+            .await
+            .unwrap();
+        }
+        let app = test::init_service(App::new().app_data(repo.clone()).route(
+            "/api/all_polls_summary",
+            web::get().to(get_all_polls_summary),
+        ))
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/all_polls_summary")
+            .insert_header(bearer(&alice))
+            .to_request();
+        let page: Vec<serde_json::Value> = test::call_and_read_body_json(&app, request).await;
+        let summaries: Vec<_> = page
+            .iter()
+            .map(|summary| {
+                (
+                    summary["question"].as_str().unwrap(),
+                    summary["vote_count"].as_i64().unwrap(),
+                    summary["has_voted"].as_bool().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(summaries, vec![("unvoted", 0, false), ("voted", 2, true)]);
+    }
+
+    #[actix_web::test]
+    async fn rejects_bad_listing_parameters() {
+        let repo = repo();
+        let app = test::init_service(App::new().app_data(repo.clone()).route(
+            "/api/all_polls_summary",
+            web::get().to(get_all_polls_summary),
+        ))
+        .await;
+
+        let newest = PollCursor::Newest {
+            id: ObjectId::new(),
+        }
+        .encode();
+        for uri in [
+            "/api/all_polls_summary?limit=0".to_string(),
+            "/api/all_polls_summary?limit=101".to_string(),
+            "/api/all_polls_summary?after=garbage".to_string(),
+            format!("/api/all_polls_summary?sort=most_votes&after={}", newest),
+        ] {
+            let response =
+                test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+            assert_eq!(response.status(), 400, "{}", uri);
+        }
+
+        let request = test::TestRequest::get()
+            .uri("/api/all_polls_summary?has_voted=true")
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 401);
+    }
+
+    #[actix_web::test]
+    async fn viewers_are_visible_to_collaborators_only() {
+        let repo = repo();
+        let owner = sign_up(&repo, "alice", Role::Member).await;
+        let stranger = sign_up(&repo, "bob", Role::Member).await;
+        let poll_id = seed_poll(&repo, "alice", "Lunch?").await.id.unwrap();
+        let app = test::init_service(App::new().app_data(repo.clone()).route(
+            "/api/polls/{poll_id}/viewers",
+            web::get().to(get_poll_viewers),
+        ))
+        .await;
+        let uri = format!("/api/polls/{}/viewers", poll_id.to_hex());
+
+        let request = test::TestRequest::get().uri(&uri).to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 401);
+
+        let request = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(bearer(&stranger))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 403);
+
+        let request = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(bearer(&owner))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["viewers"], 0);
+    }
+
+    #[actix_web::test]
+    async fn poll_details_leave_out_collaborators() {
+        let repo = repo();
+        let poll_id = seed_poll(&repo, "alice", "Lunch?").await.id.unwrap();
+        repo.update_poll_collaborators(poll_id, vec![("bob".to_string(), PollRole::Editor)])
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/polls/{poll_id}", web::get().to(get_poll_by_id)),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/polls/{}", poll_id.to_hex()))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["question"], "Lunch?");
+        assert!(body.get("collaborators").is_none());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::handlers::websocket::{open_stream, snapshot_event, PollEvent};
+use crate::realtime::registry::{self, ResumePoint, Subscription};
+use crate::realtime::shutdown;
+use crate::repositories::Repository;
+use actix_web::web::Bytes;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+
+// Comment lines sent while idle so proxies don't time the connection out.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+fn event_frame(event: &PollEvent) -> Option<Bytes> {
+    let json = serde_json::to_string(event).ok()?;
+    Some(Bytes::from(format!(
+        "id: {}:{}\ndata: {}\n\n",
+        event.epoch, event.seq, json
+    )))
+}
+
+// Streams the same events as the websocket endpoint. Each event's `id` is its
+// epoch and sequence number, so a reconnecting `EventSource` resumes from
+// where it left off through the `Last-Event-ID` header.
+pub async fn poll_events(
+    req: HttpRequest,
+    poll_id: web::Path<String>,
+    repo: web::Data<Arc<dyn Repository>>,
+) -> HttpResponse {
+    let poll_object_id = match ObjectId::parse_str(poll_id.as_str()) {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid poll ID format"),
+    };
+
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            let (epoch, seq) = value.trim().split_once(':')?;
+            Some(ResumePoint {
+                epoch: epoch.to_string(),
+                seq: seq.parse().ok()?,
+            })
+        });
+
+    let (subscription, initial_events) =
+        match open_stream(repo.get_ref().as_ref(), poll_object_id, last_event_id).await {
+            Ok(stream) => stream,
+            Err(response) => return response,
+        };
+
+    let (tx, rx) = mpsc::channel(16);
+    actix_web::rt::spawn(sse_client(
+        tx,
+        poll_object_id,
+        subscription,
+        initial_events,
+        repo.get_ref().clone(),
+    ));
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(ReceiverStream::new(rx))
+}
+
+async fn sse_client(
+    tx: mpsc::Sender<Result<Bytes, Error>>,
+    poll_id: ObjectId,
+    mut subscription: Subscription,
+    initial_events: Vec<PollEvent>,
+    repo: Arc<dyn Repository>,
+) {
+    let mut last_seq = subscription.seq;
+    let mut shutdown = shutdown::subscribe();
+    let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+    keep_alive.tick().await;
+
+    // A failed send means the client has gone away.
+    let mut closed = false;
+    for event in &initial_events {
+        if let Some(frame) = event_frame(event) {
+            if tx.send(Ok(frame)).await.is_err() {
+                closed = true;
+                break;
+            }
+        }
+    }
+
+    while !closed {
+        tokio::select! {
+            _ = shutdown.wait_for(|stopping| *stopping) => {
+                closed = true;
+            }
+            _ = keep_alive.tick() => {
+                closed = tx
+                    .send(Ok(Bytes::from_static(b": keep-alive\n\n")))
+                    .await
+                    .is_err();
+            }
+            result = subscription.receiver.recv() => {
+                let event = match result {
+                    Ok(event) if event.seq <= last_seq => continue,
+                    Ok(event) => Some(event),
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        snapshot_event(repo.as_ref(), poll_id).await.ok().flatten()
+                    }
+                    Err(broadcast::error::RecvError::Closed) => None,
+                };
+
+                match event {
+                    Some(event) => {
+                        last_seq = event.seq;
+                        if let Some(frame) = event_frame(&event) {
+                            closed = tx.send(Ok(frame)).await.is_err();
+                        }
+                    }
+                    None => closed = true,
+                }
+            }
+        }
+    }
+
+    registry::unsubscribe(&poll_id.to_hex(), subscription.id).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::test_support::{repo, seed_poll};
+    use crate::handlers::websocket::PollUpdate;
+    use actix_web::body::MessageBody;
+    use actix_web::{test, App};
+    use std::future::poll_fn;
+    use std::pin::Pin;
+
+    // Connects to the poll's event stream, resuming from `last_event_id`.
+    async fn connect(
+        repo: &web::Data<Arc<dyn Repository>>,
+        poll_id: &str,
+        last_event_id: Option<String>,
+    ) -> Pin<Box<impl MessageBody>> {
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/polls/{poll_id}/events", web::get().to(poll_events)),
+        )
+        .await;
+        let mut request = test::TestRequest::get().uri(&format!("/api/polls/{}/events", poll_id));
+        if let Some(last_event_id) = last_event_id {
+            request = request.insert_header(("Last-Event-ID", last_event_id));
+        }
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), 200);
+        Box::pin(response.into_body())
+    }
+
+    // Reads the next event from the stream, skipping keep-alive comments.
+    async fn next_event(body: &mut Pin<Box<impl MessageBody>>) -> serde_json::Value {
+        let next = async {
+            loop {
+                let chunk = poll_fn(|cx| body.as_mut().poll_next(cx)).await;
+                let chunk = chunk.expect("stream ended").ok().unwrap();
+                let frame = std::str::from_utf8(&chunk).unwrap().to_string();
+                if let Some((_, data)) = frame.split_once("data: ") {
+                    return serde_json::from_str(data.trim()).unwrap();
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), next)
+            .await
+            .expect("no event within 5s")
+    }
+
+    async fn publish_reset(poll_id: &str) {
+        registry::publish(PollUpdate::Reset {
+            poll_id: poll_id.to_string(),
+        })
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn resumes_from_the_last_event_id() {
+        let repo = repo();
+        let poll_id = seed_poll(&repo, "alice", "Resume?")
+            .await
+            .id
+            .unwrap()
+            .to_hex();
+        let start = registry::current_seq(&poll_id).await;
+        for _ in 0..3 {
+            publish_reset(&poll_id).await;
+        }
+
+        let last_event_id = format!("{}:{}", registry::epoch(), start + 1);
+        let mut events = connect(&repo, &poll_id, Some(last_event_id)).await;
+        for seq in [start + 2, start + 3] {
+            let event = next_event(&mut events).await;
+            assert_eq!(event["seq"], seq);
+            assert!(event.get("Reset").is_some(), "{}", event);
+        }
+
+        // Live updates follow the replay.
+        publish_reset(&poll_id).await;
+        assert_eq!(next_event(&mut events).await["seq"], start + 4);
+    }
+
+    #[actix_web::test]
+    async fn sends_a_snapshot_when_it_cannot_resume() {
+        let repo = repo();
+        let poll_id = seed_poll(&repo, "alice", "Snapshot?")
+            .await
+            .id
+            .unwrap()
+            .to_hex();
+        publish_reset(&poll_id).await;
+        let seq = registry::current_seq(&poll_id).await;
+
+        for last_event_id in [
+            None,
+            Some("unparseable".to_string()),
+            Some(format!("another-instance:{}", seq)),
+            Some(format!("{}:{}", registry::epoch(), seq + 10)),
+        ] {
+            let mut events = connect(&repo, &poll_id, last_event_id.clone()).await;
+            let event = next_event(&mut events).await;
+            assert!(
+                event.get("Snapshot").is_some(),
+                "{:?}: {}",
+                last_event_id,
+                event
+            );
+            assert_eq!(event["seq"], seq);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::in_memory_repository::InMemoryRepository;
+use crate::models::credential::Credential;
+use crate::models::poll::{Poll, VotingMethod};
+use crate::models::user::{Role, User};
+use crate::repositories::Repository;
+use crate::utils::session::_issue_tokens;
+use actix_web::web;
+use mongodb::bson::oid::ObjectId;
+use std::sync::{Arc, Once};
+
+static JWT_SECRET: Once = Once::new();
+
+// A fresh in-memory repository, shared with the app under test.
+pub fn repo() -> web::Data<Arc<dyn Repository>> {
+    JWT_SECRET.call_once(|| std::env::set_var("JWT_SECRET", "test-secret"));
+    web::Data::new(Arc::new(InMemoryRepository::new()) as Arc<dyn Repository>)
+}
+
+// Creates an account without hashing a password, returning an access token.
+pub async fn sign_up(repo: &web::Data<Arc<dyn Repository>>, user_id: &str, role: Role) -> String {
+    let user = User::_new(user_id.to_string(), user_id.to_string());
+    let credential = Credential::_new(user_id.to_string(), String::new());
+    repo.create_account(user, credential).await.unwrap();
+    repo.update_user_role(user_id, role).await.unwrap();
+    _issue_tokens(repo.get_ref().as_ref(), user_id)
+        .await
+        .unwrap()
+        .token
+}
+
+pub fn bearer(token: &str) -> (&'static str, String) {
+    ("Authorization", format!("Bearer {}", token))
+}
+
+// Stores a single-choice poll with two options, "a" and "b".
+pub async fn seed_poll(repo: &web::Data<Arc<dyn Repository>>, owner: &str, question: &str) -> Poll {
+    let poll = Poll {
+        id: Some(ObjectId::new()),
+        ..Poll::_new(
+            question.to_string(),
+            vec!["a".to_string(), "b".to_string()],
+            owner.to_string(),
+            false,
+            VotingMethod::Plurality,
+            None,
+        )
+    };
+    repo.create_poll(poll.clone()).await.unwrap();
+    poll
+}
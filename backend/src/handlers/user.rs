@@ -1,40 +1,483 @@
-use crate::models::user::User;
-use crate::repositories::Repository;
-use actix_web::{web, HttpResponse, Responder};
+use crate::auth::AuthenticatedUser;
+use crate::models::credential::Credential;
+use crate::models::user::{Role, User};
+use crate::policy::{self, Action};
+use crate::repositories::{AccountExists, Repository};
+use crate::utils::password;
+use crate::utils::oidc::{OidcClient, OidcError, PENDING_LOGIN_TTL};
+use crate::utils::session::{
+    _issue_tokens, _revoke_access_token, _revoke_refresh_token, _rotate_refresh_token,
+    RefreshError,
+};
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use serde::Deserialize;
 use std::sync::Arc;
 
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+// Holds the `state` of a login in progress so the callback only completes it
+// in the browser that started it.
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+
 #[derive(Debug, Deserialize)]
-pub struct LoginData {
+pub struct RegisterData {
     pub user_id: String,
     pub name: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginData {
+    pub user_id: String,
+    pub password: String,
 }
 
-// Store or Login User Handler
+// Register User Handler
+pub async fn register_handler(
+    repo: web::Data<Arc<dyn Repository>>,
+    web::Json(register_data): web::Json<RegisterData>,
+) -> impl Responder {
+    if register_data.user_id.trim().is_empty() {
+        return HttpResponse::BadRequest().body("User ID must not be empty");
+    }
+
+    // ':' is reserved for identities from external providers, e.g. "oidc:<sub>".
+    if register_data.user_id.contains(':') {
+        return HttpResponse::BadRequest().body("User ID must not contain ':'");
+    }
+
+    if register_data.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return HttpResponse::BadRequest().body(format!(
+            "Password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+
+    let password_hash = match password::_hash_password(&register_data.password) {
+        Ok(hash) => hash,
+        Err(_) => return HttpResponse::InternalServerError().body("Failed to hash password"),
+    };
+
+    let user = User::_new(register_data.user_id.clone(), register_data.name);
+    let credential = Credential::_new(register_data.user_id.clone(), password_hash);
+
+    match repo.create_account(user, credential).await {
+        Ok(_) => {}
+        Err(e) if e.is::<AccountExists>() => {
+            return HttpResponse::Conflict().body("User ID is already taken")
+        }
+        Err(e) => {
+            log::error!("Failed to create account {}: {}", register_data.user_id, e);
+            return HttpResponse::InternalServerError().body("Failed to create account");
+        }
+    }
+
+    match _issue_tokens(repo.get_ref().as_ref(), &register_data.user_id).await {
+        Ok(tokens) => HttpResponse::Ok().json(tokens),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to create JWT"),
+    }
+}
+
+// Login User Handler
 pub async fn login_handler(
     repo: web::Data<Arc<dyn Repository>>,
     web::Json(login_data): web::Json<LoginData>,
 ) -> impl Responder {
-    let user = User {
-        id: None,
-        user_id: login_data.user_id.clone(),
-        name: login_data.name.clone(),
+    let credential = match repo.find_credential(&login_data.user_id).await {
+        Ok(credential) => credential,
+        Err(e) => {
+            log::error!("Failed to load credential for {}: {}", login_data.user_id, e);
+            return HttpResponse::InternalServerError().body("Failed to log in");
+        }
+    };
+
+    let verified = match credential {
+        Some(credential) => {
+            password::_verify_password(&login_data.password, &credential.password_hash)
+        }
+        None => {
+            password::_verify_dummy_password(&login_data.password);
+            false
+        }
     };
 
-    match repo.store_user(user).await {
-        Ok(_) => match crate::utils::jwt::_create_jwt(&login_data.user_id) {
-            Ok(token) => HttpResponse::Ok().json(serde_json::json!({ "token": token })),
-            Err(_) => HttpResponse::InternalServerError().body("Failed to create JWT"),
-        },
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    if !verified {
+        return HttpResponse::Unauthorized().body("Invalid user ID or password");
+    }
+
+    match _issue_tokens(repo.get_ref().as_ref(), &login_data.user_id).await {
+        Ok(tokens) => HttpResponse::Ok().json(tokens),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to create JWT"),
     }
 }
 
+// Start OIDC Login Handler
+pub async fn oidc_login(
+    repo: web::Data<Arc<dyn Repository>>,
+    oidc: web::Data<Option<OidcClient>>,
+) -> impl Responder {
+    let Some(oidc) = oidc.as_ref() else {
+        return HttpResponse::NotFound().body("OIDC login is not configured");
+    };
+
+    match oidc.authorization_url(repo.get_ref().as_ref()).await {
+        Ok((url, state)) => HttpResponse::Found()
+            .append_header((header::LOCATION, url))
+            .cookie(_state_cookie(oidc, state, PENDING_LOGIN_TTL.as_secs() as i64))
+            .finish(),
+        Err(e @ OidcError::Storage(_)) => {
+            log::error!("Failed to start OIDC login: {}", e);
+            HttpResponse::InternalServerError().body("Failed to start login")
+        }
+        Err(e) => {
+            log::error!("Failed to start OIDC login: {}", e);
+            HttpResponse::BadGateway().body("Identity provider is unavailable")
+        }
+    }
+}
+
+// SameSite=Lax still sends the cookie on the provider's top-level redirect back.
+fn _state_cookie(oidc: &OidcClient, state: String, max_age_secs: i64) -> Cookie<'static> {
+    Cookie::build(OIDC_STATE_COOKIE, state)
+        .path("/")
+        .http_only(true)
+        .secure(oidc.config().redirect_uri.starts_with("https://"))
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(max_age_secs))
+        .finish()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+// OIDC Callback Handler
+pub async fn oidc_callback(
+    req: HttpRequest,
+    repo: web::Data<Arc<dyn Repository>>,
+    oidc: web::Data<Option<OidcClient>>,
+    query: web::Query<OidcCallbackQuery>,
+) -> impl Responder {
+    let Some(oidc) = oidc.as_ref() else {
+        return HttpResponse::NotFound().body("OIDC login is not configured");
+    };
+
+    if let Some(error) = &query.error {
+        return HttpResponse::Unauthorized().body(format!("Login was rejected: {}", error));
+    }
+
+    let (Some(code), Some(state)) = (&query.code, &query.state) else {
+        return HttpResponse::BadRequest().body("Missing code or state");
+    };
+
+    if req.cookie(OIDC_STATE_COOKIE).map(|c| c.value().to_string()).as_ref() != Some(state) {
+        return HttpResponse::Unauthorized().body("Login was not started in this browser");
+    }
+    let clear_state = _state_cookie(oidc, String::new(), 0);
+
+    let claims = match oidc.complete_login(repo.get_ref().as_ref(), code, state).await {
+        Ok(claims) => claims,
+        Err(e @ OidcError::Provider(_)) => {
+            log::error!("Failed to complete OIDC login: {}", e);
+            return HttpResponse::BadGateway().body("Identity provider is unavailable");
+        }
+        Err(e @ OidcError::Storage(_)) => {
+            log::error!("Failed to complete OIDC login: {}", e);
+            return HttpResponse::InternalServerError().body("Failed to complete login");
+        }
+        Err(OidcError::InvalidState) => {
+            return HttpResponse::Unauthorized().body("Login expired; please start again")
+        }
+        Err(e) => {
+            log::warn!("Rejected OIDC login: {}", e);
+            return HttpResponse::Unauthorized().body("Login could not be verified");
+        }
+    };
+
+    let user_id = format!("oidc:{}", claims.sub);
+    let user = User::_new(user_id.clone(), claims.display_name());
+
+    if let Err(e) = repo.store_user(user).await {
+        log::error!("Failed to store user {}: {}", user_id, e);
+        return HttpResponse::InternalServerError().body("Failed to complete login");
+    }
+
+    let tokens = match _issue_tokens(repo.get_ref().as_ref(), &user_id).await {
+        Ok(tokens) => tokens,
+        Err(_) => return HttpResponse::InternalServerError().body("Failed to create JWT"),
+    };
+
+    match &oidc.config().post_login_redirect {
+        Some(redirect) => HttpResponse::Found()
+            .append_header((
+                header::LOCATION,
+                format!(
+                    "{}#token={}&refresh_token={}&expires_in={}",
+                    redirect, tokens.token, tokens.refresh_token, tokens.expires_in
+                ),
+            ))
+            .cookie(clear_state)
+            .finish(),
+        None => HttpResponse::Ok().cookie(clear_state).json(tokens),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshData {
+    pub refresh_token: String,
+}
+
+// Refresh Token Handler
+pub async fn refresh_handler(
+    repo: web::Data<Arc<dyn Repository>>,
+    web::Json(refresh_data): web::Json<RefreshData>,
+) -> impl Responder {
+    match _rotate_refresh_token(repo.get_ref().as_ref(), &refresh_data.refresh_token).await {
+        Ok(tokens) => HttpResponse::Ok().json(tokens),
+        Err(RefreshError::Invalid) => HttpResponse::Unauthorized().body("Invalid refresh token"),
+        Err(RefreshError::Expired) => HttpResponse::Unauthorized().body("Refresh token has expired"),
+        Err(RefreshError::Reused) => {
+            HttpResponse::Unauthorized().body("Refresh token was already used; please log in again")
+        }
+        Err(RefreshError::Internal) => {
+            HttpResponse::InternalServerError().body("Failed to refresh token")
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogoutData {
+    pub refresh_token: Option<String>,
+}
+
+// Logout Handler
+pub async fn logout_handler(
+    repo: web::Data<Arc<dyn Repository>>,
+    auth: Option<BearerAuth>,
+    logout_data: Option<web::Json<LogoutData>>,
+) -> impl Responder {
+    if let Some(auth) = auth {
+        if _revoke_access_token(repo.get_ref().as_ref(), auth.token()).await.is_err() {
+            return HttpResponse::Unauthorized().body("Invalid token");
+        }
+    }
+
+    if let Some(refresh_token) = logout_data.and_then(|data| data.into_inner().refresh_token) {
+        if _revoke_refresh_token(repo.get_ref().as_ref(), &refresh_token).await.is_err() {
+            return HttpResponse::InternalServerError().body("Failed to revoke refresh token");
+        }
+    }
+
+    HttpResponse::Ok().body("Logged out successfully")
+}
+
 // Get User ID from JWT
-pub async fn get_user_id(auth: BearerAuth) -> impl Responder {
-    match crate::utils::jwt::_verify_jwt(auth.token()) {
-        Ok(user_id) => HttpResponse::Ok().json(serde_json::json!({ "user_id": user_id })),
-        Err(_) => HttpResponse::Unauthorized().body("Invalid or expired token"),
+pub async fn get_user_id(user: AuthenticatedUser) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "user_id": user.user_id }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleRequest {
+    pub role: Role,
+}
+
+// Update User Role Handler
+pub async fn update_user_role(
+    repo: web::Data<Arc<dyn Repository>>,
+    user_id: web::Path<String>,
+    body: web::Json<RoleRequest>,
+    user: AuthenticatedUser,
+) -> impl Responder {
+    if !policy::can(&user, Action::ManageRoles) {
+        return policy::forbidden("You are not authorized to change user roles.");
+    }
+
+    match repo.update_user_role(&user_id, body.role).await {
+        Ok(true) => HttpResponse::Ok().body("User role updated successfully"),
+        Ok(false) => HttpResponse::NotFound().body("User not found"),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to update user role"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::test_support::{bearer, repo, sign_up};
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn registers_and_logs_in() {
+        let repo = repo();
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/register", web::post().to(register_handler))
+                .route("/api/login", web::post().to(login_handler)),
+        )
+        .await;
+        let account = serde_json::json!({
+            "user_id": "alice",
+            "name": "Alice",
+            "password": "correct horse",
+        });
+
+        let request = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(&account)
+            .to_request();
+        let tokens: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert!(tokens["token"].is_string());
+        assert!(tokens["refresh_token"].is_string());
+
+        let request = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(&account)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 409);
+
+        for (password, status) in [("correct horse", 200), ("wrong horse", 401)] {
+            let request = test::TestRequest::post()
+                .uri("/api/login")
+                .set_json(serde_json::json!({ "user_id": "alice", "password": password }))
+                .to_request();
+            assert_eq!(test::call_service(&app, request).await.status(), status);
+        }
+
+        let request = test::TestRequest::post()
+            .uri("/api/login")
+            .set_json(serde_json::json!({ "user_id": "nobody", "password": "correct horse" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 401);
+    }
+
+    #[actix_web::test]
+    async fn refuses_to_claim_users_without_a_password() {
+        let repo = repo();
+        repo.store_user(User::_new("legacy".to_string(), "Legacy".to_string()))
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/register", web::post().to(register_handler)),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/api/register")
+            .set_json(serde_json::json!({
+                "user_id": "legacy",
+                "name": "Mallory",
+                "password": "long enough",
+            }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 409);
+        assert!(repo.find_credential("legacy").await.unwrap().is_none());
+        let user = repo.find_user_by_id("legacy").await.unwrap().unwrap();
+        assert_eq!(user.name, "Legacy");
+    }
+
+    #[actix_web::test]
+    async fn rejects_invalid_registrations() {
+        let repo = repo();
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/register", web::post().to(register_handler)),
+        )
+        .await;
+
+        for (user_id, password) in [
+            (" ", "long enough"),
+            ("oidc:x", "long enough"),
+            ("bob", "short"),
+        ] {
+            let request = test::TestRequest::post()
+                .uri("/api/register")
+                .set_json(serde_json::json!({
+                    "user_id": user_id,
+                    "name": "Bob",
+                    "password": password,
+                }))
+                .to_request();
+            assert_eq!(
+                test::call_service(&app, request).await.status(),
+                400,
+                "{}",
+                user_id
+            );
+        }
+    }
+
+    fn refresh(refresh_token: &str) -> actix_web::test::TestRequest {
+        test::TestRequest::post()
+            .uri("/api/refresh")
+            .set_json(serde_json::json!({ "refresh_token": refresh_token }))
+    }
+
+    #[actix_web::test]
+    async fn reusing_a_refresh_token_revokes_its_family() {
+        let repo = repo();
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/refresh", web::post().to(refresh_handler)),
+        )
+        .await;
+        let first = _issue_tokens(repo.get_ref().as_ref(), "alice").await.unwrap();
+
+        let second: serde_json::Value =
+            test::call_and_read_body_json(&app, refresh(&first.refresh_token).to_request()).await;
+        let second = second["refresh_token"].as_str().unwrap().to_string();
+        assert_ne!(second, first.refresh_token);
+
+        // The replayed token is refused, and so is the one it was rotated into.
+        for token in [&first.refresh_token, &second] {
+            let response = test::call_service(&app, refresh(token).to_request()).await;
+            assert_eq!(response.status(), 401);
+        }
+
+        // Other sessions of the same user are left alone.
+        let other = _issue_tokens(repo.get_ref().as_ref(), "alice").await.unwrap();
+        let response = test::call_service(&app, refresh(&other.refresh_token).to_request()).await;
+        assert_eq!(response.status(), 200);
+    }
+
+    #[actix_web::test]
+    async fn logged_out_tokens_are_refused() {
+        let repo = repo();
+        sign_up(&repo, "alice", Role::Member).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/refresh", web::post().to(refresh_handler))
+                .route("/api/logout", web::post().to(logout_handler))
+                .route("/api/get_user_id", web::get().to(get_user_id)),
+        )
+        .await;
+        let tokens = _issue_tokens(repo.get_ref().as_ref(), "alice").await.unwrap();
+        let whoami = || {
+            test::TestRequest::get()
+                .uri("/api/get_user_id")
+                .insert_header(bearer(&tokens.token))
+                .to_request()
+        };
+        assert_eq!(test::call_service(&app, whoami()).await.status(), 200);
+
+        let request = test::TestRequest::post()
+            .uri("/api/logout")
+            .insert_header(bearer(&tokens.token))
+            .set_json(serde_json::json!({ "refresh_token": tokens.refresh_token }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 200);
+
+        assert_eq!(test::call_service(&app, whoami()).await.status(), 401);
+        let response = test::call_service(&app, refresh(&tokens.refresh_token).to_request()).await;
+        assert_eq!(response.status(), 401);
     }
 }
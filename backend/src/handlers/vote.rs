@@ -1,28 +1,43 @@
+use crate::models::poll::PublicPoll;
 use crate::models::vote::Vote;
-use crate::repositories::Repository;
+use crate::auth::AuthenticatedUser;
+use crate::policy::{self, Action, PollAction};
+use crate::realtime::fanout;
+use crate::repositories::{Repository, VoteConflict};
+use crate::validation::{validate_vote, SubmittedVote, VoteValidationError};
 use actix_web::{web, HttpResponse, Responder};
-use actix_web_httpauth::extractors::bearer::BearerAuth;
 use mongodb::bson::oid::ObjectId;
 use serde::Deserialize;
 use std::sync::Arc;
 
-use super::websocket::{broadcast_poll_update, PollUpdate, VoteResult};
+use super::websocket::{broadcast_poll_update, PollUpdate};
 
 #[derive(Deserialize)]
 pub struct VoteData {
     pub poll_id: String,
+    #[serde(default)]
     pub option_ids: Vec<String>,
+    // `(option_id, score)` pairs for score polls.
+    #[serde(default)]
+    pub scores: Vec<(String, i32)>,
+}
+
+impl From<VoteData> for SubmittedVote {
+    fn from(vote_data: VoteData) -> Self {
+        SubmittedVote {
+            poll_id: vote_data.poll_id,
+            option_ids: vote_data.option_ids,
+            scores: vote_data.scores,
+        }
+    }
 }
 
 // Get Voted Polls Handler
 pub async fn get_voted_polls(
     repo: web::Data<Arc<dyn Repository>>,
-    auth: BearerAuth,
+    user: AuthenticatedUser,
 ) -> impl Responder {
-    let user_id = match crate::utils::jwt::_verify_jwt(auth.token()) {
-        Ok(user_id) => user_id,
-        Err(_) => return HttpResponse::Unauthorized().body("Invalid token"),
-    };
+    let user_id = user.user_id;
 
     let votes = match repo.find_votes_by_user(&user_id).await {
         Ok(votes) => votes,
@@ -36,6 +51,7 @@ pub async fn get_voted_polls(
         Err(_) => return HttpResponse::InternalServerError().body("Failed to retrieve polls"),
     };
 
+    let voted_polls: Vec<PublicPoll> = voted_polls.into_iter().map(PublicPoll::from).collect();
     HttpResponse::Ok().json(voted_polls)
 }
 
@@ -43,16 +59,11 @@ pub async fn get_voted_polls(
 pub async fn get_vote_by_poll_and_user(
     repo: web::Data<Arc<dyn Repository>>,
     params: web::Path<(String, String)>,
-    auth: BearerAuth,
+    user: AuthenticatedUser,
 ) -> impl Responder {
     let (poll_id, user_id) = params.into_inner();
-    let token_user_id = match crate::utils::jwt::_verify_jwt(auth.token()) {
-        Ok(user_id) => user_id,
-        Err(_) => return HttpResponse::Unauthorized().body("Invalid token"),
-    };
-
-    if token_user_id != user_id {
-        return HttpResponse::Unauthorized().body("Unauthorized access");
+    if user.user_id != user_id && !policy::can(&user, Action::ViewAnyVote) {
+        return policy::forbidden("Only admins can view other users' votes");
     }
 
     let poll_object_id = match ObjectId::parse_str(&poll_id) {
@@ -67,61 +78,95 @@ pub async fn get_vote_by_poll_and_user(
     }
 }
 
-// Submit or Update Vote Handler
-pub async fn submit_or_update_vote(
-    repo: web::Data<Arc<dyn Repository>>,
-    vote_data: web::Json<VoteData>,
-    auth: BearerAuth,
-) -> impl Responder {
-    let user_id = match crate::utils::jwt::_verify_jwt(auth.token()) {
-        Ok(user_id) => user_id,
-        Err(_) => return HttpResponse::Unauthorized().body("Invalid token"),
-    };
+// Why a vote was not recorded.
+#[derive(Debug)]
+pub enum CastVoteError {
+    Forbidden,
+    Invalid(VoteValidationError),
+    Conflict,
+    Storage,
+}
 
-    let poll_object_id = match ObjectId::parse_str(&vote_data.poll_id) {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::BadRequest().body("Invalid poll ID format"),
-    };
+impl CastVoteError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CastVoteError::Forbidden => "forbidden",
+            CastVoteError::Invalid(e) => e.code(),
+            CastVoteError::Conflict => "vote_conflict",
+            CastVoteError::Storage => "internal_error",
+        }
+    }
 
-    let option_ids: Vec<ObjectId> = match vote_data
-        .option_ids
-        .iter()
-        .map(|id| ObjectId::parse_str(id))
-        .collect::<Result<Vec<_>, _>>()
-    {
-        Ok(ids) => ids,
-        Err(_) => return HttpResponse::BadRequest().body("Invalid option ID format"),
-    };
+    pub fn message(&self) -> String {
+        match self {
+            CastVoteError::Forbidden => "You are not allowed to vote.".to_string(),
+            CastVoteError::Invalid(e) => e.message(),
+            CastVoteError::Conflict => {
+                "Another vote from this account was submitted at the same time".to_string()
+            }
+            CastVoteError::Storage => "Failed to submit vote".to_string(),
+        }
+    }
+
+    pub fn to_response(&self) -> HttpResponse {
+        match self {
+            CastVoteError::Forbidden => policy::forbidden(&self.message()),
+            CastVoteError::Invalid(e) => e.to_response(),
+            CastVoteError::Conflict => HttpResponse::Conflict().json(serde_json::json!({
+                "error": self.code(),
+                "message": self.message(),
+            })),
+            CastVoteError::Storage => HttpResponse::InternalServerError().body(self.message()),
+        }
+    }
+}
+
+// Checks, records and broadcasts a user's vote. Used by the REST handler and
+// by votes cast over a poll's websocket.
+pub async fn cast_vote(
+    repo: &Arc<dyn Repository>,
+    user: &AuthenticatedUser,
+    vote_data: &SubmittedVote,
+) -> Result<(), CastVoteError> {
+    if !policy::can(user, Action::Vote) {
+        return Err(CastVoteError::Forbidden);
+    }
+
+    let validated = validate_vote(repo.as_ref(), vote_data)
+        .await
+        .map_err(CastVoteError::Invalid)?;
+    let poll_object_id = validated.poll_id;
 
     let vote = Vote {
         id: None,
         poll_id: poll_object_id,
-        user_id: user_id.clone(),
-        option_ids,
+        user_id: user.user_id.clone(),
+        option_ids: validated.option_ids,
+        scores: validated.scores,
     };
 
+    if let Err(e) = repo.submit_or_update_vote(vote).await {
+        return Err(if e.is::<VoteConflict>() {
+            CastVoteError::Conflict
+        } else {
+            CastVoteError::Storage
+        });
+    }
 
+    fanout::fanout().vote_changed(repo.clone(), poll_object_id);
 
-    match repo.submit_or_update_vote(vote).await {
-        Ok(_) => {
-            if let Ok(results) = repo.get_poll_results(poll_object_id).await {
-                let results_vec: Vec<VoteResult> = results
-                    .into_iter()
-                    .map(|(id, count)| VoteResult {
-                        _id: id.to_hex(),
-                        count,
-                    })
-                    .collect();
+    Ok(())
+}
 
-                broadcast_poll_update(PollUpdate::VoteUpdate {
-                    poll_id: vote_data.poll_id.clone(),
-                    results: results_vec,
-                })
-                .await;
-            }
-            HttpResponse::Ok().body("Vote submitted successfully")
-        }
-        Err(_) => HttpResponse::InternalServerError().body("Failed to submit vote"),
+// Submit or Update Vote Handler
+pub async fn submit_or_update_vote(
+    repo: web::Data<Arc<dyn Repository>>,
+    vote_data: web::Json<VoteData>,
+    user: AuthenticatedUser,
+) -> impl Responder {
+    match cast_vote(repo.get_ref(), &user, &vote_data.into_inner().into()).await {
+        Ok(()) => HttpResponse::Ok().body("Vote submitted successfully"),
+        Err(e) => e.to_response(),
     }
 }
 
@@ -129,13 +174,8 @@ pub async fn submit_or_update_vote(
 pub async fn reset_votes(
     repo: web::Data<Arc<dyn Repository>>,
     poll_id: web::Path<String>,
-    auth: BearerAuth,
+    user: AuthenticatedUser,
 ) -> impl Responder {
-    let user_id = match crate::utils::jwt::_verify_jwt(auth.token()) {
-        Ok(user_id) => user_id,
-        Err(_) => return HttpResponse::Unauthorized().body("Invalid token"),
-    };
-
     let poll_id_str = poll_id.to_string();
 
     let poll_object_id = match ObjectId::parse_str(&poll_id_str) {
@@ -144,7 +184,7 @@ pub async fn reset_votes(
     };
 
     match repo.get_poll_by_id(poll_object_id).await {
-        Ok(Some(poll)) if poll.created_by == user_id => {
+        Ok(Some(poll)) if policy::can_on_poll(&user, &poll, PollAction::ResetVotes) => {
             match repo.reset_votes_for_poll(poll_object_id).await {
                 Ok(_) => {
                     broadcast_poll_update(PollUpdate::Reset {
@@ -156,10 +196,151 @@ pub async fn reset_votes(
                 Err(_) => HttpResponse::InternalServerError().body("Failed to reset votes"),
             }
         }
-        Ok(Some(_)) => HttpResponse::Unauthorized().json(serde_json::json!({
-            "message": "You are not authorized to reset votes for this poll."
-        })),
+        Ok(Some(_)) => policy::forbidden("You are not authorized to reset votes for this poll."),
         Ok(None) => HttpResponse::NotFound().body("Poll not found"),
         Err(_) => HttpResponse::InternalServerError().body("Failed to retrieve poll"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::test_support::{bearer, repo, seed_poll, sign_up};
+    use crate::models::poll::Poll;
+    use crate::models::user::Role;
+    use actix_web::{test, App};
+
+    fn ballot(poll: &Poll, option: usize) -> serde_json::Value {
+        serde_json::json!({
+            "poll_id": poll.id.unwrap().to_hex(),
+            "option_ids": [poll.options[option].0.to_hex()],
+        })
+    }
+
+    #[actix_web::test]
+    async fn votes_are_counted_once_per_user() {
+        let repo = repo();
+        let token = sign_up(&repo, "alice", Role::Member).await;
+        let poll = seed_poll(&repo, "alice", "Lunch?").await;
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/vote", web::post().to(submit_or_update_vote)),
+        )
+        .await;
+
+        for option in [0, 1] {
+            let request = test::TestRequest::post()
+                .uri("/api/vote")
+                .insert_header(bearer(&token))
+                .set_json(ballot(&poll, option))
+                .to_request();
+            assert_eq!(test::call_service(&app, request).await.status(), 200);
+        }
+
+        let results = repo.get_poll_results(poll.id.unwrap()).await.unwrap();
+        assert_eq!(results, vec![(poll.options[1].0, 1)]);
+    }
+
+    #[actix_web::test]
+    async fn invalid_and_unauthorized_votes_are_refused() {
+        let repo = repo();
+        let viewer = sign_up(&repo, "bob", Role::Viewer).await;
+        let member = sign_up(&repo, "carol", Role::Member).await;
+        let poll = seed_poll(&repo, "alice", "Lunch?").await;
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/vote", web::post().to(submit_or_update_vote)),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/api/vote")
+            .insert_header(bearer(&viewer))
+            .set_json(ballot(&poll, 0))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 403);
+
+        let mut both = ballot(&poll, 0);
+        both["option_ids"] =
+            serde_json::json!([poll.options[0].0.to_hex(), poll.options[1].0.to_hex(),]);
+        let request = test::TestRequest::post()
+            .uri("/api/vote")
+            .insert_header(bearer(&member))
+            .set_json(both)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 422);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "too_many_options");
+    }
+
+    #[actix_web::test]
+    async fn only_admins_read_other_users_votes() {
+        let repo = repo();
+        let alice = sign_up(&repo, "alice", Role::Member).await;
+        let bob = sign_up(&repo, "bob", Role::Member).await;
+        let admin = sign_up(&repo, "root", Role::Admin).await;
+        let poll = seed_poll(&repo, "alice", "Lunch?").await;
+        let poll_id = poll.id.unwrap();
+        repo.submit_or_update_vote(Vote::_new(
+            poll_id,
+            vec![poll.options[0].0],
+            "alice".to_string(),
+        ))
+        .await
+        .unwrap();
+        let app = test::init_service(App::new().app_data(repo.clone()).route(
+            "/api/votes/{poll_id}/{user_id}",
+            web::get().to(get_vote_by_poll_and_user),
+        ))
+        .await;
+        let uri = format!("/api/votes/{}/alice", poll_id.to_hex());
+
+        for (token, status) in [(&alice, 200), (&bob, 403), (&admin, 200)] {
+            let request = test::TestRequest::get()
+                .uri(&uri)
+                .insert_header(bearer(token))
+                .to_request();
+            assert_eq!(test::call_service(&app, request).await.status(), status);
+        }
+    }
+
+    #[actix_web::test]
+    async fn only_owners_reset_votes() {
+        let repo = repo();
+        let owner = sign_up(&repo, "alice", Role::Member).await;
+        let stranger = sign_up(&repo, "bob", Role::Member).await;
+        let poll = seed_poll(&repo, "alice", "Lunch?").await;
+        let poll_id = poll.id.unwrap();
+        repo.submit_or_update_vote(Vote::_new(
+            poll_id,
+            vec![poll.options[0].0],
+            "bob".to_string(),
+        ))
+        .await
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(repo.clone())
+                .route("/api/reset_votes/{poll_id}", web::delete().to(reset_votes)),
+        )
+        .await;
+        let uri = format!("/api/reset_votes/{}", poll_id.to_hex());
+
+        let request = test::TestRequest::delete()
+            .uri(&uri)
+            .insert_header(bearer(&stranger))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 403);
+        assert!(!repo.get_poll_results(poll_id).await.unwrap().is_empty());
+
+        let request = test::TestRequest::delete()
+            .uri(&uri)
+            .insert_header(bearer(&owner))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 200);
+        assert!(repo.get_poll_results(poll_id).await.unwrap().is_empty());
+    }
+}
@@ -1,11 +1,22 @@
+use crate::auth::authenticate;
+use crate::handlers::vote::cast_vote;
+use crate::models::poll::PublicPoll;
+use crate::realtime::registry::{self, ResumePoint, Subscription};
+use crate::realtime::{fanout, shutdown};
+use crate::repositories::Repository;
+use crate::validation::SubmittedVote;
 use actix_web::{web, Error, HttpRequest, HttpResponse};
-use actix_ws::{Message, MessageStream, Session};
+use actix_ws::{
+    AggregatedMessage, AggregatedMessageStream, CloseCode, CloseReason, ProtocolError, Session,
+};
 use futures_util::StreamExt;
-use tokio::sync::broadcast::{self, Sender};
-use serde::Serialize;
-use std::collections::HashMap;
-use tokio::sync::RwLock;
+use mongodb::bson::oid::ObjectId;
+use tokio::sync::{broadcast, watch};
+use serde::{Deserialize, Serialize};
 use once_cell::sync::Lazy;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone, Serialize)]
 pub struct VoteResult {
@@ -13,115 +24,728 @@ pub struct VoteResult {
     pub count: i32,
 }
 
+impl VoteResult {
+    pub fn from_results(results: Vec<(ObjectId, i32)>) -> Vec<VoteResult> {
+        results
+            .into_iter()
+            .map(|(id, count)| VoteResult {
+                _id: id.to_hex(),
+                count,
+            })
+            .collect()
+    }
+}
+
 #[derive(Clone, Serialize)]
 pub enum PollUpdate {
+    // Full state of the poll, sent first on every new connection.
+    Snapshot {
+        poll_id: String,
+        poll: PublicPoll,
+        results: Vec<VoteResult>,
+        is_active: bool,
+    },
     VoteUpdate { poll_id: String, results: Vec<VoteResult> },
     StatusUpdate { poll_id: String, is_active: bool },
     Reset { poll_id: String },
+    // Number of live connections watching the poll.
+    Presence { poll_id: String, viewers: usize },
+}
+
+impl PollUpdate {
+    pub fn poll_id(&self) -> &str {
+        match self {
+            PollUpdate::Snapshot { poll_id, .. }
+            | PollUpdate::VoteUpdate { poll_id, .. }
+            | PollUpdate::StatusUpdate { poll_id, .. }
+            | PollUpdate::Reset { poll_id }
+            | PollUpdate::Presence { poll_id, .. } => poll_id,
+        }
+    }
+}
+
+// What goes over the wire: an update and its per-poll sequence number, e.g.
+// `{"epoch": "...", "seq": 7, "VoteUpdate": {...}}`. A snapshot carries the
+// sequence number of the last update it reflects, and every later update
+// carries a higher one. Sequence numbers only compare within an epoch (see
+// `registry::epoch`).
+#[derive(Clone, Serialize)]
+pub struct PollEvent {
+    pub epoch: &'static str,
+    pub seq: u64,
+    #[serde(flatten)]
+    pub update: PollUpdate,
+}
+
+// Loads the current state of a poll, or `None` if it does not exist.
+pub async fn load_snapshot(
+    repo: &dyn Repository,
+    poll_id: ObjectId,
+) -> Result<Option<PollUpdate>, Box<dyn std::error::Error>> {
+    let Some(poll) = repo.get_poll_by_id(poll_id).await? else {
+        return Ok(None);
+    };
+    let results = repo.get_poll_results(poll_id).await?;
+
+    Ok(Some(PollUpdate::Snapshot {
+        poll_id: poll_id.to_hex(),
+        is_active: poll.isactive,
+        poll: poll.into(),
+        results: VoteResult::from_results(results),
+    }))
+}
+
+// Connection limits, read once from the environment:
+// WS_PING_INTERVAL_SECS (default 15), WS_MAX_MISSED_PONGS (default 2) and
+// WS_MAX_MESSAGE_SIZE in bytes (default 64 KiB).
+struct WsConfig {
+    ping_interval: Duration,
+    max_missed_pongs: u32,
+    max_message_size: usize,
+}
+
+impl WsConfig {
+    fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        }
+
+        WsConfig {
+            ping_interval: Duration::from_secs(var("WS_PING_INTERVAL_SECS", 15u64).max(1)),
+            max_missed_pongs: var("WS_MAX_MISSED_PONGS", 2),
+            max_message_size: var("WS_MAX_MESSAGE_SIZE", 64 * 1024),
+        }
+    }
+}
+
+static WS_CONFIG: Lazy<WsConfig> = Lazy::new(WsConfig::from_env);
+
+#[derive(Deserialize)]
+pub struct WsQuery {
+    // Epoch and sequence number of the last event the client applied. Without
+    // a matching epoch the client gets a snapshot instead of a replay.
+    pub resume_from: Option<u64>,
+    pub epoch: Option<String>,
+    // Access token, for clients that can't authenticate with a first message.
+    pub token: Option<String>,
 }
 
+// Messages a client may send over a poll's socket.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    // Authenticates the connection with an access token.
+    Auth {
+        token: String,
+    },
+    // Casts or changes the user's vote on this socket's poll.
+    Vote {
+        request_id: Option<String>,
+        #[serde(default)]
+        option_ids: Vec<String>,
+        #[serde(default)]
+        scores: Vec<(String, i32)>,
+    },
+}
 
-// Using a simple counter for unique session IDs
-static SESSION_COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+// Replies to a `ClientMessage`, sent alongside the poll's events.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerReply {
+    Ack {
+        request_id: Option<String>,
+    },
+    Error {
+        request_id: Option<String>,
+        error: String,
+        message: String,
+    },
+}
 
-// Modified structure to store Session with a unique ID
-struct SessionWrapper {
-    id: usize,
-    _session: Session,
+impl ServerReply {
+    fn error(request_id: Option<String>, error: &str, message: impl Into<String>) -> Self {
+        ServerReply::Error {
+            request_id,
+            error: error.to_string(),
+            message: message.into(),
+        }
+    }
 }
 
-static POLL_UPDATES: Lazy<(Sender<PollUpdate>, RwLock<HashMap<String, Vec<SessionWrapper>>>)> = Lazy::new(|| {
-    let (tx, _) = broadcast::channel(100);
-    (tx, RwLock::new(HashMap::new()))
-});
+// Loads a snapshot stamped with the sequence number it is current as of.
+pub async fn snapshot_event(
+    repo: &dyn Repository,
+    poll_id: ObjectId,
+) -> Result<Option<PollEvent>, Box<dyn std::error::Error>> {
+    // Read the sequence number first: the snapshot then reflects at least
+    // every update up to it.
+    let seq = registry::current_seq(&poll_id.to_hex()).await;
+    Ok(load_snapshot(repo, poll_id)
+        .await?
+        .map(|update| PollEvent {
+            epoch: registry::epoch(),
+            seq,
+            update,
+        }))
+}
+
+// Subscribes to a poll and loads the events a new stream starts with: the
+// updates missed since `resume_from` when they are still buffered, otherwise
+// a snapshot. On failure the subscription is dropped and an error response
+// returned.
+pub async fn open_stream(
+    repo: &dyn Repository,
+    poll_id: ObjectId,
+    resume_from: Option<ResumePoint>,
+) -> Result<(Subscription, Vec<PollEvent>), HttpResponse> {
+    // Subscribe before reading the snapshot so no update falls in between.
+    // Updates are full state, so one already reflected in the snapshot is
+    // harmless to apply again.
+    let mut subscription = registry::subscribe(&poll_id.to_hex(), resume_from).await;
+
+    let initial_events = match subscription.replay.take() {
+        Some(replay) => repo
+            .get_poll_by_id(poll_id)
+            .await
+            .map(|poll| poll.map(|_| replay)),
+        None => load_snapshot(repo, poll_id).await.map(|snapshot| {
+            snapshot.map(|update| {
+                vec![PollEvent {
+                    epoch: registry::epoch(),
+                    seq: subscription.seq,
+                    update,
+                }]
+            })
+        }),
+    };
+
+    match initial_events {
+        Ok(Some(events)) => Ok((subscription, events)),
+        Ok(None) => {
+            registry::unsubscribe(&poll_id.to_hex(), subscription.id).await;
+            Err(HttpResponse::NotFound().body("Poll not found"))
+        }
+        Err(_) => {
+            registry::unsubscribe(&poll_id.to_hex(), subscription.id).await;
+            Err(HttpResponse::InternalServerError().body("Failed to retrieve poll"))
+        }
+    }
+}
 
 pub async fn ws_handler(
     req: HttpRequest,
     stream: web::Payload,
     poll_id: web::Path<String>,
+    query: web::Query<WsQuery>,
+    repo: web::Data<Arc<dyn Repository>>,
 ) -> Result<HttpResponse, Error> {
-    let (response, session, msg_stream) = actix_ws::handle(&req, stream)?;
-    
-    let session_id = SESSION_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-    let session_wrapper = SessionWrapper {
-        id: session_id,
-        _session: session.clone(),
+    let poll_object_id = match ObjectId::parse_str(poll_id.as_str()) {
+        Ok(id) => id,
+        Err(_) => return Ok(HttpResponse::BadRequest().body("Invalid poll ID format")),
     };
-    
-    // Store the session for this poll
-    let mut connections = POLL_UPDATES.1.write().await;
-    connections
-        .entry(poll_id.to_string())
-        .or_default()
-        .push(session_wrapper);
-
-    // Spawn WebSocket handler task
-    actix_web::rt::spawn(ws_client(
-        session,
-        msg_stream,
-        poll_id.to_string(),
-        session_id,
-        POLL_UPDATES.0.subscribe(),
-    ));
-
-    Ok(response)
+    let resume_from = match (query.resume_from, query.epoch.clone()) {
+        (Some(seq), Some(epoch)) => Some(ResumePoint { epoch, seq }),
+        _ => None,
+    };
+
+    // A token in the query string is checked up front, like a REST request.
+    let token = query.into_inner().token;
+    if let Some(token) = &token {
+        if let Err(e) = authenticate(token, repo.get_ref().as_ref()).await {
+            return Ok(e.error_response());
+        }
+    }
+
+    let (subscription, initial_events) =
+        match open_stream(repo.get_ref().as_ref(), poll_object_id, resume_from).await {
+            Ok(stream) => stream,
+            Err(response) => return Ok(response),
+        };
+
+    WsClient {
+        poll_id: poll_object_id,
+        subscription,
+        initial_events,
+        repo: repo.get_ref().clone(),
+        token,
+        config: &WS_CONFIG,
+        shutdown: shutdown::subscribe(),
+    }
+    .start(&req, stream)
+    .await
+}
+
+async fn send_event(session: &mut Session, event: &PollEvent) -> bool {
+    match serde_json::to_string(event) {
+        Ok(json) => session.text(json).await.is_ok(),
+        Err(_) => true,
+    }
+}
+
+async fn send_reply(session: &mut Session, reply: &ServerReply) -> bool {
+    match serde_json::to_string(reply) {
+        Ok(json) => session.text(json).await.is_ok(),
+        Err(_) => true,
+    }
+}
+
+// Handles one text frame from the client. The connection's token is verified
+// again for every vote so an expired or revoked token stops working, as it
+// would for the REST API.
+async fn handle_client_message(
+    repo: &Arc<dyn Repository>,
+    poll_id: ObjectId,
+    token: &mut Option<String>,
+    text: &str,
+) -> ServerReply {
+    let message = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(message) => message,
+        Err(e) => return ServerReply::error(None, "invalid_message", e.to_string()),
+    };
+
+    match message {
+        ClientMessage::Auth { token: new_token } => {
+            match authenticate(&new_token, repo.as_ref()).await {
+                Ok(_) => {
+                    *token = Some(new_token);
+                    ServerReply::Ack { request_id: None }
+                }
+                Err(_) => ServerReply::error(None, "unauthorized", "Invalid token"),
+            }
+        }
+        ClientMessage::Vote {
+            request_id,
+            option_ids,
+            scores,
+        } => {
+            let Some(current_token) = token.as_deref() else {
+                return ServerReply::error(
+                    request_id,
+                    "unauthorized",
+                    "Authenticate before voting",
+                );
+            };
+            let user = match authenticate(current_token, repo.as_ref()).await {
+                Ok(user) => user,
+                Err(_) => return ServerReply::error(request_id, "unauthorized", "Invalid token"),
+            };
+
+            let vote_data = SubmittedVote {
+                poll_id: poll_id.to_hex(),
+                option_ids,
+                scores,
+            };
+            match cast_vote(repo, &user, &vote_data).await {
+                Ok(()) => ServerReply::Ack { request_id },
+                Err(e) => ServerReply::error(request_id, e.code(), e.message()),
+            }
+        }
+    }
+}
+
+fn close_reason(code: CloseCode, description: &str) -> Option<CloseReason> {
+    Some(CloseReason {
+        code,
+        description: Some(description.to_string()),
+    })
+}
+
+// A subscribed socket waiting for its handshake, with the settings and
+// shutdown signal its session runs under.
+struct WsClient {
+    poll_id: ObjectId,
+    subscription: Subscription,
+    initial_events: Vec<PollEvent>,
+    repo: Arc<dyn Repository>,
+    token: Option<String>,
+    config: &'static WsConfig,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl WsClient {
+    // Completes the handshake and runs the session on its own task.
+    async fn start(self, req: &HttpRequest, stream: web::Payload) -> Result<HttpResponse, Error> {
+        let (response, session, msg_stream) = match actix_ws::handle(req, stream) {
+            Ok(handshake) => handshake,
+            Err(e) => {
+                registry::unsubscribe(&self.poll_id.to_hex(), self.subscription.id).await;
+                return Err(e);
+            }
+        };
+
+        let msg_stream = msg_stream
+            .max_frame_size(self.config.max_message_size)
+            .aggregate_continuations()
+            .max_continuation_size(self.config.max_message_size);
+
+        actix_web::rt::spawn(ws_client(session, msg_stream, self));
+        Ok(response)
+    }
 }
 
 async fn ws_client(
     mut session: Session,
-    mut msg_stream: MessageStream,
-    poll_id: String,
-    session_id: usize,
-    mut broadcast_rx: broadcast::Receiver<PollUpdate>,
+    mut msg_stream: AggregatedMessageStream,
+    client: WsClient,
 ) {
-    let mut closed = false;
+    let WsClient {
+        poll_id,
+        mut subscription,
+        initial_events,
+        repo,
+        mut token,
+        config,
+        mut shutdown,
+    } = client;
+    let mut last_seq = subscription.seq;
+
+    // The first tick completes immediately, so the first ping goes out one
+    // interval after connecting.
+    let mut heartbeat = tokio::time::interval(config.ping_interval);
+    heartbeat.tick().await;
+    let mut missed_pongs = 0;
+
+    let mut sent = true;
+    for event in &initial_events {
+        sent = send_event(&mut session, event).await;
+        if !sent {
+            break;
+        }
+    }
+
+    // A send fails only once the session is closed, so those paths stop
+    // without a close frame.
+    let reason = loop {
+        if !sent {
+            break None;
+        }
 
-    while !closed {
         tokio::select! {
-            Some(msg) = msg_stream.next() => {
+            msg = msg_stream.next() => {
+                // Any frame shows the peer is still there.
+                missed_pongs = 0;
                 match msg {
-                    Ok(Message::Close(_)) => {
-                        closed = true;
+                    Some(Ok(AggregatedMessage::Close(reason))) => break reason,
+                    Some(Ok(AggregatedMessage::Ping(bytes))) => {
+                        sent = session.pong(&bytes).await.is_ok();
                     }
-                    Ok(Message::Ping(bytes)) => {
-                        if session.pong(&bytes).await.is_err() {
-                            closed = true;
-                        }
+                    Some(Ok(AggregatedMessage::Text(text))) => {
+                        let reply =
+                            handle_client_message(&repo, poll_id, &mut token, &text).await;
+                        sent = send_reply(&mut session, &reply).await;
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(ProtocolError::Overflow)) => {
+                        break close_reason(CloseCode::Size, "Message too large");
                     }
-                    Err(_) => {
-                        closed = true;
+                    // Covers oversized fragmented messages too, which the
+                    // aggregated stream can't tell apart from other I/O errors.
+                    Some(Err(ProtocolError::Io(_))) => {
+                        break close_reason(CloseCode::Error, "Failed to read message");
                     }
-                    _ => {}
+                    Some(Err(_)) => break close_reason(CloseCode::Protocol, "Protocol error"),
+                    None => break None,
                 }
             }
-            Ok(update) = broadcast_rx.recv() => {
-                match &update {
-                    PollUpdate::VoteUpdate { poll_id: update_poll_id, .. } |
-                    PollUpdate::StatusUpdate { poll_id: update_poll_id, .. } |
-                    PollUpdate::Reset { poll_id: update_poll_id } => {
-                        if *update_poll_id == poll_id {
-                            if let Ok(json) = serde_json::to_string(&update) {
-                                if session.text(json).await.is_err() {
-                                    closed = true;
-                                }
+            _ = heartbeat.tick() => {
+                if missed_pongs >= config.max_missed_pongs {
+                    break close_reason(CloseCode::Policy, "Heartbeat timeout");
+                }
+                missed_pongs += 1;
+                sent = session.ping(b"").await.is_ok();
+            }
+            _ = shutdown.wait_for(|stopping| *stopping) => {
+                break close_reason(CloseCode::Away, "Server shutting down");
+            }
+            result = subscription.receiver.recv() => {
+                match result {
+                    // Skip events already covered by a snapshot sent after a lag.
+                    Ok(event) if event.seq <= last_seq => {}
+                    Ok(event) => {
+                        last_seq = event.seq;
+                        sent = send_event(&mut session, &event).await;
+                    }
+                    // The client fell too far behind to replay what it missed,
+                    // so it gets the current state instead.
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        match snapshot_event(repo.as_ref(), poll_id).await {
+                            Ok(Some(snapshot)) => {
+                                last_seq = snapshot.seq;
+                                sent = send_event(&mut session, &snapshot).await;
                             }
+                            _ => break close_reason(CloseCode::Error, "Failed to reload poll"),
                         }
                     }
+                    Err(broadcast::error::RecvError::Closed) => break None,
                 }
             }
         }
+    };
+
+    let _ = session.close(reason).await;
+    registry::unsubscribe(&poll_id.to_hex(), subscription.id).await;
+}
+
+pub async fn broadcast_poll_update(update: PollUpdate) {
+    fanout::fanout().publish(update).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::test_support::{repo, seed_poll, sign_up};
+    use crate::models::user::Role;
+    use actix_codec::Framed;
+    use actix_http::ws::{Codec, Frame, Message};
+    use actix_web::{App, HttpServer};
+    use futures_util::SinkExt;
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    type Client = Framed<TcpStream, Codec>;
+
+    type Settings = (&'static WsConfig, watch::Receiver<bool>);
+
+    // Short enough for a test to wait out.
+    static QUICK: WsConfig = WsConfig {
+        ping_interval: Duration::from_millis(100),
+        max_missed_pongs: 2,
+        max_message_size: 1024,
+    };
+
+    // Like `ws_handler`, but with the given settings and shutdown signal.
+    async fn configured_handler(
+        req: HttpRequest,
+        stream: web::Payload,
+        poll_id: web::Path<String>,
+        repo: web::Data<Arc<dyn Repository>>,
+        settings: web::Data<Settings>,
+    ) -> Result<HttpResponse, Error> {
+        let poll_id = ObjectId::parse_str(poll_id.as_str()).unwrap();
+        let (subscription, initial_events) = open_stream(repo.get_ref().as_ref(), poll_id, None)
+            .await
+            .unwrap();
+        WsClient {
+            poll_id,
+            subscription,
+            initial_events,
+            repo: repo.get_ref().clone(),
+            token: None,
+            config: settings.0,
+            shutdown: settings.1.clone(),
+        }
+        .start(&req, stream)
+        .await
+    }
+
+    // Serves the socket endpoint on a free local port, through `ws_handler`
+    // unless other settings are given.
+    fn serve(repo: &web::Data<Arc<dyn Repository>>, settings: Option<Settings>) -> SocketAddr {
+        let repo = repo.clone();
+        let server = HttpServer::new(move || {
+            let app = App::new().app_data(repo.clone());
+            match settings.clone() {
+                Some(settings) => app
+                    .app_data(web::Data::new(settings))
+                    .route("/ws/{poll_id}", web::get().to(configured_handler)),
+                None => app.route("/ws/{poll_id}", web::get().to(ws_handler)),
+            }
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        address
+    }
+
+    async fn connect(address: SocketAddr, path: &str) -> Client {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path, address
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        // Read the response head a byte at a time so no frame is consumed with it.
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap();
+        assert!(head.starts_with("HTTP/1.1 101"), "{}", head);
+        Framed::new(stream, Codec::new().client_mode())
     }
 
-    // Clean up connection
-    let mut connections = POLL_UPDATES.1.write().await;
-    if let Some(poll_sessions) = connections.get_mut(&poll_id) {
-        poll_sessions.retain(|wrapper| wrapper.id != session_id);
-        if poll_sessions.is_empty() {
-            connections.remove(&poll_id);
+    async fn next_frame(client: &mut Client) -> Frame {
+        tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("no frame within 5s")
+            .expect("socket closed")
+            .unwrap()
+    }
+
+    // The next event or reply, skipping pings.
+    async fn next_message(client: &mut Client) -> serde_json::Value {
+        loop {
+            match next_frame(client).await {
+                Frame::Text(text) => return serde_json::from_slice(&text).unwrap(),
+                Frame::Ping(_) => {}
+                frame => panic!("unexpected frame: {:?}", frame),
+            }
         }
     }
-}
 
-pub async fn broadcast_poll_update(update: PollUpdate) {
-    let _ = POLL_UPDATES.0.send(update);
-}
\ No newline at end of file
+    // The reply to the last client message, skipping poll events.
+    async fn next_reply(client: &mut Client) -> serde_json::Value {
+        loop {
+            let message = next_message(client).await;
+            if message.get("type").is_some() {
+                return message;
+            }
+        }
+    }
+
+    async fn send(client: &mut Client, message: serde_json::Value) {
+        let text = message.to_string().into();
+        client.send(Message::Text(text)).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn votes_cast_over_the_socket_are_counted() {
+        let repo = repo();
+        let token = sign_up(&repo, "alice", Role::Member).await;
+        let poll = seed_poll(&repo, "bob", "Socket?").await;
+        let (poll_id, option_a) = (poll.id.unwrap(), poll.options[0].0);
+
+        let path = format!("/ws/{}?token={}", poll_id, token);
+        let mut client = connect(serve(&repo, None), &path).await;
+        assert!(next_message(&mut client).await.get("Snapshot").is_some());
+
+        let vote = json!({ "type": "vote", "request_id": "1", "option_ids": [option_a.to_hex()] });
+        send(&mut client, vote).await;
+        assert_eq!(
+            next_reply(&mut client).await,
+            json!({ "type": "ack", "request_id": "1" })
+        );
+        let stored = repo.find_vote(poll_id, "alice").await.unwrap().unwrap();
+        assert_eq!(stored.option_ids, vec![option_a]);
+
+        // The new count reaches the socket once the broadcast window passes.
+        loop {
+            let message = next_message(&mut client).await;
+            if let Some(update) = message.get("VoteUpdate") {
+                assert_eq!(update["results"][0]["count"], 1, "{}", message);
+                break;
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn votes_over_the_socket_need_a_valid_token_and_ballot() {
+        let repo = repo();
+        let member = sign_up(&repo, "alice", Role::Member).await;
+        let viewer = sign_up(&repo, "victor", Role::Viewer).await;
+        let poll = seed_poll(&repo, "bob", "Socket?").await;
+        let poll_id = poll.id.unwrap();
+
+        let mut client = connect(serve(&repo, None), &format!("/ws/{}", poll_id)).await;
+        assert!(next_message(&mut client).await.get("Snapshot").is_some());
+        let vote = |option_id: ObjectId| json!({ "type": "vote", "request_id": "1", "option_ids": [option_id.to_hex()] });
+        let error = |reply: serde_json::Value| {
+            assert_eq!(reply["type"], "error", "{}", reply);
+            reply["error"].as_str().unwrap().to_string()
+        };
+
+        send(&mut client, vote(poll.options[0].0)).await;
+        assert_eq!(error(next_reply(&mut client).await), "unauthorized");
+
+        send(
+            &mut client,
+            json!({ "type": "auth", "token": "not-a-token" }),
+        )
+        .await;
+        assert_eq!(error(next_reply(&mut client).await), "unauthorized");
+
+        send(&mut client, json!({ "type": "auth", "token": viewer })).await;
+        assert_eq!(next_reply(&mut client).await["type"], "ack");
+        send(&mut client, vote(poll.options[0].0)).await;
+        assert_eq!(error(next_reply(&mut client).await), "forbidden");
+
+        send(&mut client, json!({ "type": "auth", "token": member })).await;
+        assert_eq!(next_reply(&mut client).await["type"], "ack");
+        send(&mut client, vote(ObjectId::new())).await;
+        assert_eq!(error(next_reply(&mut client).await), "unknown_option");
+
+        send(&mut client, json!({ "type": "hello" })).await;
+        assert_eq!(error(next_reply(&mut client).await), "invalid_message");
+
+        assert!(repo.find_vote(poll_id, "alice").await.unwrap().is_none());
+        assert!(repo.find_vote(poll_id, "victor").await.unwrap().is_none());
+    }
+
+    // Reads until the server closes the socket, without answering pings.
+    async fn close_code(client: &mut Client) -> CloseCode {
+        loop {
+            match next_frame(client).await {
+                Frame::Close(Some(reason)) => return reason.code,
+                Frame::Close(None) => panic!("closed without a code"),
+                _ => {}
+            }
+        }
+    }
+
+    // Opens a socket on a fresh poll under `QUICK` settings.
+    async fn connect_quickly(shutdown: watch::Receiver<bool>) -> Client {
+        let repo = repo();
+        let poll = seed_poll(&repo, "bob", "Quick?").await;
+        let address = serve(&repo, Some((&QUICK, shutdown)));
+        let mut client = connect(address, &format!("/ws/{}", poll.id.unwrap())).await;
+        assert!(next_message(&mut client).await.get("Snapshot").is_some());
+        client
+    }
+
+    #[actix_web::test]
+    async fn closes_sockets_that_stop_answering_pings() {
+        let (_shutdown, stopping) = watch::channel(false);
+        let mut client = connect_quickly(stopping).await;
+
+        for _ in 0..5 {
+            match next_frame(&mut client).await {
+                Frame::Ping(bytes) => client.send(Message::Pong(bytes)).await.unwrap(),
+                frame => panic!("unexpected frame: {:?}", frame),
+            }
+        }
+
+        assert_eq!(close_code(&mut client).await, CloseCode::Policy);
+    }
+
+    #[actix_web::test]
+    async fn closes_sockets_sent_oversized_messages() {
+        let (_shutdown, stopping) = watch::channel(false);
+        let mut client = connect_quickly(stopping).await;
+
+        let fits = "x".repeat(QUICK.max_message_size);
+        client.send(Message::Text(fits.into())).await.unwrap();
+        assert_eq!(next_reply(&mut client).await["error"], "invalid_message");
+
+        let oversized = "x".repeat(QUICK.max_message_size + 1);
+        client.send(Message::Text(oversized.into())).await.unwrap();
+        assert_eq!(close_code(&mut client).await, CloseCode::Size);
+    }
+
+    #[actix_web::test]
+    async fn closes_sockets_as_going_away_on_shutdown() {
+        let (shutdown, stopping) = watch::channel(false);
+        let mut client = connect_quickly(stopping).await;
+
+        shutdown.send_replace(true);
+        assert_eq!(close_code(&mut client).await, CloseCode::Away);
+    }
+}
@@ -0,0 +1,567 @@
+use crate::models::credential::Credential;
+use crate::models::token::{PendingLogin, RefreshToken, RevokedToken};
+use crate::models::user::{Role, User};
+use crate::models::{
+    poll::{Poll, PollRole},
+    vote::Vote,
+};
+use crate::repositories::poll_query::{PollCursor, PollPage, PollQuery, PollStatusFilter};
+use crate::repositories::{
+    AccountExists, LeaseRepository,
+    PollRepository, Repository, TallyCheck, TokenRepository, UserRepository, VoteRepository,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+// Keeps every collection in process memory, mirroring the behaviour of
+// `MongoDBRepository` so the API can run without a database.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    polls: RwLock<Vec<Poll>>,
+    votes: RwLock<Vec<Vote>>,
+    // Per-option counters by poll, only written while `votes` is locked.
+    tallies: RwLock<HashMap<ObjectId, Vec<(ObjectId, i32)>>>,
+    users: RwLock<Vec<User>>,
+    credentials: RwLock<Vec<Credential>>,
+    refresh_tokens: RwLock<Vec<RefreshToken>>,
+    revoked_tokens: RwLock<Vec<RevokedToken>>,
+    pending_logins: RwLock<Vec<PendingLogin>>,
+    // Holder and expiry by lease name.
+    leases: RwLock<HashMap<String, (String, Instant)>>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryRepository {
+    async fn store_user(&self, user: User) -> Result<(), Box<dyn Error>> {
+        let mut users = self.users.write().await;
+        if !users.iter().any(|u| u.user_id == user.user_id) {
+            users.push(User {
+                id: Some(user.id.unwrap_or_default()),
+                ..user
+            });
+        }
+        Ok(())
+    }
+
+    async fn find_user_by_id(&self, user_id: &str) -> Result<Option<User>, Box<dyn Error>> {
+        let users = self.users.read().await;
+        Ok(users.iter().find(|u| u.user_id == user_id).cloned())
+    }
+
+    async fn find_users_by_ids(&self, user_ids: Vec<String>) -> Result<Vec<User>, Box<dyn Error>> {
+        let users = self.users.read().await;
+        Ok(users
+            .iter()
+            .filter(|u| user_ids.contains(&u.user_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn update_user_role(&self, user_id: &str, role: Role) -> Result<bool, Box<dyn Error>> {
+        let mut users = self.users.write().await;
+        match users.iter_mut().find(|u| u.user_id == user_id) {
+            Some(user) => {
+                user.role = role;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn create_account(&self, user: User, credential: Credential) -> Result<(), Box<dyn Error>> {
+        let mut credentials = self.credentials.write().await;
+        let mut users = self.users.write().await;
+        if credentials.iter().any(|c| c.user_id == credential.user_id)
+            || users.iter().any(|u| u.user_id == user.user_id)
+        {
+            return Err(Box::new(AccountExists));
+        }
+
+        users.push(User {
+            id: Some(user.id.unwrap_or_default()),
+            ..user
+        });
+        credentials.push(Credential {
+            id: Some(credential.id.unwrap_or_default()),
+            ..credential
+        });
+        Ok(())
+    }
+
+    async fn find_credential(&self, user_id: &str) -> Result<Option<Credential>, Box<dyn Error>> {
+        let credentials = self.credentials.read().await;
+        Ok(credentials.iter().find(|c| c.user_id == user_id).cloned())
+    }
+}
+
+#[async_trait]
+impl PollRepository for InMemoryRepository {
+    async fn create_poll(&self, poll: Poll) -> Result<(), Box<dyn Error>> {
+        self.polls.write().await.push(Poll {
+            id: Some(poll.id.unwrap_or_default()),
+            ..poll
+        });
+        Ok(())
+    }
+
+    async fn get_poll_by_id(&self, id: ObjectId) -> Result<Option<Poll>, Box<dyn Error>> {
+        let polls = self.polls.read().await;
+        Ok(polls.iter().find(|p| p.id == Some(id)).cloned())
+    }
+
+    async fn find_polls_by_ids(
+        &self,
+        poll_ids: Vec<ObjectId>,
+    ) -> Result<Vec<Poll>, Box<dyn Error>> {
+        let polls = self.polls.read().await;
+        Ok(polls
+            .iter()
+            .filter(|p| p.id.is_some_and(|id| poll_ids.contains(&id)))
+            .cloned()
+            .collect())
+    }
+
+    async fn list_polls(&self, query: &PollQuery) -> Result<PollPage, Box<dyn Error>> {
+        let polls = self.polls.read().await;
+        let votes = self.votes.read().await;
+
+        let voted_poll_ids: Option<(HashSet<ObjectId>, bool)> =
+            query.voted_by.as_ref().map(|(user_id, has_voted)| {
+                let ids = votes
+                    .iter()
+                    .filter(|v| &v.user_id == user_id)
+                    .map(|v| v.poll_id)
+                    .collect();
+                (ids, *has_voted)
+            });
+
+        let mut matching: Vec<(PollCursor, &Poll, i64)> = polls
+            .iter()
+            .filter(|p| match query.status {
+                Some(PollStatusFilter::Active) => p.isactive,
+                Some(PollStatusFilter::Closed) => !p.isactive,
+                None => true,
+            })
+            .filter(|p| {
+                query
+                    .created_by
+                    .as_ref()
+                    .is_none_or(|user_id| &p.created_by == user_id)
+            })
+            .filter(|p| query.created_after.is_none_or(|after| p.created_at >= after))
+            .filter(|p| query.created_before.is_none_or(|before| p.created_at < before))
+            .filter(|p| {
+                voted_poll_ids.as_ref().is_none_or(|(ids, has_voted)| {
+                    p.id.is_some_and(|id| ids.contains(&id)) == *has_voted
+                })
+            })
+            .map(|p| {
+                let vote_count = votes.iter().filter(|v| Some(v.poll_id) == p.id).count() as i64;
+                (PollCursor::at(p, query.sort, vote_count), p, vote_count)
+            })
+            .filter(|(cursor, _, _)| {
+                query
+                    .after
+                    .as_ref()
+                    .is_none_or(|after| cursor.cmp_in_order(after) == Ordering::Greater)
+            })
+            .collect();
+        matching.sort_by(|(a, _, _), (b, _, _)| a.cmp_in_order(b));
+
+        let has_more = matching.len() > query.limit;
+        matching.truncate(query.limit);
+
+        Ok(PollPage {
+            next_cursor: if has_more {
+                matching.last().map(|(cursor, _, _)| cursor.clone())
+            } else {
+                None
+            },
+            ballot_counts: matching
+                .iter()
+                .filter_map(|(_, p, vote_count)| p.id.map(|id| (id, *vote_count)))
+                .collect(),
+            polls: matching.into_iter().map(|(_, p, _)| p.clone()).collect(),
+        })
+    }
+
+    async fn update_poll_status(&self, id: ObjectId, is_active: bool) -> Result<(), Box<dyn Error>> {
+        let mut polls = self.polls.write().await;
+        if let Some(poll) = polls.iter_mut().find(|p| p.id == Some(id)) {
+            poll.isactive = is_active;
+            poll.opens_at = None;
+            if !is_active {
+                poll.closes_at = None;
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply_poll_schedule(&self, poll: &Poll, is_active: bool) -> Result<bool, Box<dyn Error>> {
+        let mut polls = self.polls.write().await;
+        let Some(current) = polls.iter_mut().find(|p| {
+            p.id.is_some()
+                && p.id == poll.id
+                && p.isactive == poll.isactive
+                && p.opens_at == poll.opens_at
+                && p.closes_at == poll.closes_at
+        }) else {
+            return Ok(false);
+        };
+
+        current.isactive = is_active;
+        current.opens_at = None;
+        if !is_active {
+            current.closes_at = None;
+        }
+        Ok(true)
+    }
+
+    async fn get_scheduled_polls(&self) -> Result<Vec<Poll>, Box<dyn Error>> {
+        let polls = self.polls.read().await;
+        Ok(polls
+            .iter()
+            .filter(|p| p.opens_at.is_some() || p.closes_at.is_some())
+            .cloned()
+            .collect())
+    }
+
+    async fn update_poll_collaborators(
+        &self,
+        id: ObjectId,
+        collaborators: Vec<(String, PollRole)>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut polls = self.polls.write().await;
+        if let Some(poll) = polls.iter_mut().find(|p| p.id == Some(id)) {
+            poll.collaborators = collaborators;
+        }
+        Ok(())
+    }
+
+    async fn reset_votes_for_poll(&self, poll_id: ObjectId) -> Result<(), Box<dyn Error>> {
+        let mut votes = self.votes.write().await;
+        votes.retain(|v| v.poll_id != poll_id);
+        self.tallies.write().await.remove(&poll_id);
+        Ok(())
+    }
+
+    async fn get_poll_results(&self, poll_id: ObjectId) -> Result<Vec<(ObjectId, i32)>, Box<dyn Error>> {
+        let tallies = self.tallies.read().await;
+        Ok(tallies
+            .get(&poll_id)
+            .map(|counts| {
+                counts
+                    .iter()
+                    .copied()
+                    .filter(|(_, count)| *count != 0)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn reconcile_poll_results(
+        &self,
+        poll_id: ObjectId,
+        repair: bool,
+    ) -> Result<TallyCheck, Box<dyn Error>> {
+        let votes = self.votes.read().await;
+        let mut tallies = self.tallies.write().await;
+
+        let stored: Vec<(ObjectId, i32)> = tallies
+            .get(&poll_id)
+            .map(|counts| {
+                counts
+                    .iter()
+                    .copied()
+                    .filter(|(_, count)| *count != 0)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Same tally as the `$unwind`/`$group` pipeline: every entry of
+        // `option_ids` counts once, options nobody picked are omitted.
+        let mut recounted: Vec<(ObjectId, i32)> = Vec::new();
+        for option_id in votes
+            .iter()
+            .filter(|v| v.poll_id == poll_id)
+            .flat_map(|v| v.option_ids.iter())
+        {
+            add_count(&mut recounted, option_id, 1);
+        }
+
+        if repair {
+            tallies.insert(poll_id, recounted.clone());
+        }
+
+        Ok(TallyCheck { stored, recounted })
+    }
+}
+
+fn add_count(counts: &mut Vec<(ObjectId, i32)>, option_id: &ObjectId, delta: i32) {
+    match counts.iter_mut().find(|(id, _)| id == option_id) {
+        Some((_, count)) => *count += delta,
+        None => counts.push((*option_id, delta)),
+    }
+}
+
+#[async_trait]
+impl VoteRepository for InMemoryRepository {
+    async fn find_voted_poll_ids(
+        &self,
+        user_id: &str,
+        poll_ids: Vec<ObjectId>,
+    ) -> Result<Vec<ObjectId>, Box<dyn Error>> {
+        let votes = self.votes.read().await;
+        Ok(votes
+            .iter()
+            .filter(|v| v.user_id == user_id && poll_ids.contains(&v.poll_id))
+            .map(|v| v.poll_id)
+            .collect())
+    }
+
+    async fn find_votes_by_user(&self, user_id: &str) -> Result<Vec<Vote>, Box<dyn Error>> {
+        let votes = self.votes.read().await;
+        Ok(votes
+            .iter()
+            .filter(|v| v.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_vote(
+        &self,
+        poll_id: ObjectId,
+        user_id: &str,
+    ) -> Result<Option<Vote>, Box<dyn Error>> {
+        let votes = self.votes.read().await;
+        Ok(votes
+            .iter()
+            .find(|v| v.poll_id == poll_id && v.user_id == user_id)
+            .cloned())
+    }
+
+    async fn find_votes_by_poll(&self, poll_id: ObjectId) -> Result<Vec<Vote>, Box<dyn Error>> {
+        let votes = self.votes.read().await;
+        Ok(votes
+            .iter()
+            .filter(|v| v.poll_id == poll_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn submit_or_update_vote(&self, vote: Vote) -> Result<(), Box<dyn Error>> {
+        let mut votes = self.votes.write().await;
+        let mut tallies = self.tallies.write().await;
+        let counts = tallies.entry(vote.poll_id).or_default();
+
+        for option_id in &vote.option_ids {
+            add_count(counts, option_id, 1);
+        }
+
+        match votes
+            .iter_mut()
+            .find(|v| v.poll_id == vote.poll_id && v.user_id == vote.user_id)
+        {
+            Some(existing_vote) => {
+                for option_id in &existing_vote.option_ids {
+                    add_count(counts, option_id, -1);
+                }
+                existing_vote.option_ids = vote.option_ids;
+                existing_vote.scores = vote.scores;
+            }
+            None => votes.push(Vote {
+                id: Some(vote.id.unwrap_or_default()),
+                ..vote
+            }),
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TokenRepository for InMemoryRepository {
+    async fn store_refresh_token(&self, token: RefreshToken) -> Result<(), Box<dyn Error>> {
+        self.refresh_tokens.write().await.push(RefreshToken {
+            id: Some(token.id.unwrap_or_default()),
+            ..token
+        });
+        Ok(())
+    }
+
+    async fn find_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, Box<dyn Error>> {
+        let tokens = self.refresh_tokens.read().await;
+        Ok(tokens.iter().find(|t| t.token_hash == token_hash).cloned())
+    }
+
+    async fn mark_refresh_token_used(&self, token_hash: &str) -> Result<bool, Box<dyn Error>> {
+        let mut tokens = self.refresh_tokens.write().await;
+        match tokens
+            .iter_mut()
+            .find(|t| t.token_hash == token_hash && !t.used)
+        {
+            Some(token) => {
+                token.used = true;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn revoke_token_family(&self, family_id: &str) -> Result<(), Box<dyn Error>> {
+        let mut tokens = self.refresh_tokens.write().await;
+        for token in tokens.iter_mut().filter(|t| t.family_id == family_id) {
+            token.revoked = true;
+        }
+        Ok(())
+    }
+
+    async fn revoke_access_token(&self, token: RevokedToken) -> Result<(), Box<dyn Error>> {
+        let mut revoked = self.revoked_tokens.write().await;
+        // Expired tokens fail verification anyway, so their entries can go.
+        revoked.retain(|t| t.expires_at > Utc::now());
+        revoked.push(RevokedToken {
+            id: Some(token.id.unwrap_or_default()),
+            ..token
+        });
+        Ok(())
+    }
+
+    async fn is_access_token_revoked(&self, jti: &str) -> Result<bool, Box<dyn Error>> {
+        let revoked = self.revoked_tokens.read().await;
+        Ok(revoked.iter().any(|t| t.jti == jti))
+    }
+
+    async fn store_pending_login(&self, login: PendingLogin) -> Result<(), Box<dyn Error>> {
+        let mut logins = self.pending_logins.write().await;
+        logins.retain(|l| l.expires_at > Utc::now());
+        logins.push(PendingLogin {
+            id: Some(login.id.unwrap_or_default()),
+            ..login
+        });
+        Ok(())
+    }
+
+    async fn take_pending_login(&self, state: &str) -> Result<Option<PendingLogin>, Box<dyn Error>> {
+        let mut logins = self.pending_logins.write().await;
+        let Some(index) = logins.iter().position(|l| l.state == state) else {
+            return Ok(None);
+        };
+        Ok(Some(logins.remove(index)).filter(|l| l.expires_at > Utc::now()))
+    }
+}
+
+#[async_trait]
+impl LeaseRepository for InMemoryRepository {
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: Duration) -> Result<bool, Box<dyn Error>> {
+        let mut leases = self.leases.write().await;
+        let now = Instant::now();
+        if let Some((current, expires_at)) = leases.get(name) {
+            if current != holder && *expires_at > now {
+                return Ok(false);
+            }
+        }
+        leases.insert(name.to_string(), (holder.to_string(), now + ttl));
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(results: &[(ObjectId, i32)], option_id: ObjectId) -> i32 {
+        results
+            .iter()
+            .find(|(id, _)| *id == option_id)
+            .map(|(_, count)| *count)
+            .unwrap_or(0)
+    }
+
+    #[actix_web::test]
+    async fn votes_increment_and_decrement_counters() {
+        let repo = InMemoryRepository::new();
+        let (poll_id, a, b) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+
+        repo.submit_or_update_vote(Vote::_new(poll_id, vec![a], "alice".to_string()))
+            .await
+            .unwrap();
+        repo.submit_or_update_vote(Vote::_new(poll_id, vec![a], "bob".to_string()))
+            .await
+            .unwrap();
+        let results = repo.get_poll_results(poll_id).await.unwrap();
+        assert_eq!(count(&results, a), 2);
+
+        // Changing a ballot moves its count rather than adding one.
+        repo.submit_or_update_vote(Vote::_new(poll_id, vec![b], "bob".to_string()))
+            .await
+            .unwrap();
+        let results = repo.get_poll_results(poll_id).await.unwrap();
+        assert_eq!(count(&results, a), 1);
+        assert_eq!(count(&results, b), 1);
+
+        // Options that drop to zero are left out.
+        repo.submit_or_update_vote(Vote::_new(poll_id, vec![b], "alice".to_string()))
+            .await
+            .unwrap();
+        let results = repo.get_poll_results(poll_id).await.unwrap();
+        assert_eq!(results, vec![(b, 2)]);
+
+        let mut check = repo.reconcile_poll_results(poll_id, false).await.unwrap();
+        check.stored.sort();
+        check.recounted.sort();
+        assert_eq!(check.stored, check.recounted);
+    }
+
+    #[actix_web::test]
+    async fn resetting_votes_clears_counters() {
+        let repo = InMemoryRepository::new();
+        let (poll_id, a) = (ObjectId::new(), ObjectId::new());
+
+        repo.submit_or_update_vote(Vote::_new(poll_id, vec![a], "alice".to_string()))
+            .await
+            .unwrap();
+        repo.reset_votes_for_poll(poll_id).await.unwrap();
+
+        assert!(repo.get_poll_results(poll_id).await.unwrap().is_empty());
+        assert!(repo.find_votes_by_poll(poll_id).await.unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn pending_logins_complete_once() {
+        let repo = InMemoryRepository::new();
+        let login = |state: &str, expires_in| {
+            PendingLogin::_new(
+                state.to_string(),
+                "verifier".to_string(),
+                "nonce".to_string(),
+                Utc::now() + chrono::Duration::seconds(expires_in),
+            )
+        };
+        repo.store_pending_login(login("fresh", 600)).await.unwrap();
+        repo.store_pending_login(login("stale", -1)).await.unwrap();
+
+        let taken = repo.take_pending_login("fresh").await.unwrap().unwrap();
+        assert_eq!(taken.code_verifier, "verifier");
+        assert!(repo.take_pending_login("fresh").await.unwrap().is_none());
+        assert!(repo.take_pending_login("stale").await.unwrap().is_none());
+    }
+}
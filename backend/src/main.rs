@@ -1,36 +1,96 @@
 use actix_cors::Cors;
-use actix_web::{web::{self}, App, HttpServer};
+use actix_web::{middleware::from_fn, web::{self}, App, HttpServer};
+use auth::require_auth;
 use repositories::Repository;
 use std::sync::Arc;
 use handlers::websocket::ws_handler;
 use std::env;
 
+mod auth;
 mod handlers;
 mod models;
 mod utils;
 mod repositories;
 mod mongodb_repository;
+mod in_memory_repository;
+mod validation;
+mod tally;
+mod scheduler;
+mod policy;
+mod realtime;
+mod migrations;
+mod reconciliation;
 
+use in_memory_repository::InMemoryRepository;
+use mongodb::Database;
+use realtime::fanout::{self, ChangeStreamFanout};
 use mongodb_repository::MongoDBRepository;
-use utils::db::_get_database_client;
+use utils::db::{_get_database_client, _supports_transactions};
+use utils::oidc::{OidcClient, OidcConfig};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let client = _get_database_client()
-        .await
-        .expect("Failed to create MongoDB client");
+    dotenv::dotenv().ok();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let mongo_repo: Arc<dyn Repository> = Arc::new(MongoDBRepository::new(&client));
+    // REPOSITORY=memory runs the API without a database; anything else uses MongoDB.
+    let (repo, database): (Arc<dyn Repository>, Option<Database>) =
+        match env::var("REPOSITORY").as_deref() {
+            Ok("memory") => (Arc::new(InMemoryRepository::new()), None),
+            _ => {
+                let client = _get_database_client()
+                    .await
+                    .expect("Failed to create MongoDB client");
+                let transactions = _supports_transactions(&client)
+                    .await
+                    .expect("Failed to reach MongoDB");
+                if !transactions {
+                    log::warn!(
+                        "MongoDB is not a replica set; vote counters are updated without \
+                         transactions and rely on tally reconciliation to repair drift"
+                    );
+                }
+                let mongo_repo = MongoDBRepository::new(&client, transactions);
+                let database = mongo_repo.database().clone();
+                (Arc::new(mongo_repo), Some(database))
+            }
+        };
 
-    let repo_data = web::Data::new(mongo_repo);
+    // Pending migrations run before serving. `--migrate` applies them and
+    // exits, for deployments that migrate as a separate step.
+    let migrate_only = env::args().any(|arg| arg == "--migrate");
+    if let Some(database) = &database {
+        if let Err(e) = migrations::run(database).await {
+            panic!("Failed to apply migrations: {}", e);
+        }
+    }
+    if migrate_only {
+        return Ok(());
+    }
 
-    dotenv::dotenv().ok();
+    // REALTIME_FANOUT=change_streams relays updates between replicas through
+    // MongoDB; by default updates only reach this process's connections.
+    if env::var("REALTIME_FANOUT").as_deref() == Ok("change_streams") {
+        let database = database
+            .as_ref()
+            .expect("REALTIME_FANOUT=change_streams requires the MongoDB repository");
+        fanout::install(ChangeStreamFanout::start(database, repo.clone()));
+    }
+
+    policy::promote_bootstrap_admins(repo.as_ref()).await;
+
+    scheduler::spawn(repo.clone());
+    reconciliation::spawn(repo.clone());
+
+    let repo_data = web::Data::new(repo);
+    let oidc_data = web::Data::new(OidcConfig::from_env().map(OidcClient::new));
 
     let origin = env::var("ORIGIN").expect("ORIGIN must be set");
 
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         App::new()
             .app_data(repo_data.clone())
+            .app_data(oidc_data.clone())
             .wrap(
                 Cors::default()
                     .allowed_origin(&origin)
@@ -39,13 +99,24 @@ async fn main() -> std::io::Result<()> {
                         actix_web::http::header::CONTENT_TYPE,
                         actix_web::http::header::AUTHORIZATION,
                     ])
+                    .expose_headers(vec!["X-Next-Cursor"])
                     .supports_credentials()
                     .max_age(3600),
             )
             .route("/ws/{poll_id}", web::get().to(ws_handler))
 
+            .route("/api/register", web::post().to(handlers::user::register_handler))
             .route("/api/login", web::post().to(handlers::user::login_handler))
-            .route("/api/get_user_id", web::get().to(handlers::user::get_user_id))
+            .route("/api/refresh", web::post().to(handlers::user::refresh_handler))
+            .route("/api/logout", web::post().to(handlers::user::logout_handler))
+            .route("/api/oidc/login", web::get().to(handlers::user::oidc_login))
+            .route("/api/oidc/callback", web::get().to(handlers::user::oidc_callback))
+            .route(
+                "/api/get_user_id",
+                web::get()
+                    .to(handlers::user::get_user_id)
+                    .wrap(from_fn(require_auth)),
+            )
             .route(
                 "/api/all_polls_summary",
                 web::get().to(handlers::poll::get_all_polls_summary),
@@ -54,40 +125,125 @@ async fn main() -> std::io::Result<()> {
                 "/api/polls/{poll_id}",
                 web::get().to(handlers::poll::get_poll_by_id),
             )
+            .route(
+                "/api/polls/{poll_id}/events",
+                web::get().to(handlers::sse::poll_events),
+            )
+            .route(
+                "/api/polls/{poll_id}/viewers",
+                web::get()
+                    .to(handlers::poll::get_poll_viewers)
+                    .wrap(from_fn(require_auth)),
+            )
             .route(
                 "/api/polls/user/{user_id}",
                 web::get().to(handlers::poll::get_polls_by_user),
             )
             .route(
                 "/api/create_polls",
-                web::post().to(handlers::poll::create_poll),
+                web::post()
+                    .to(handlers::poll::create_poll)
+                    .wrap(from_fn(require_auth)),
             )
             .route(
                 "/api/vote",
-                web::post().to(handlers::vote::submit_or_update_vote),
+                web::post()
+                    .to(handlers::vote::submit_or_update_vote)
+                    .wrap(from_fn(require_auth)),
             )
             .route(
                 "/api/my_votes",
-                web::get().to(handlers::vote::get_voted_polls),
+                web::get()
+                    .to(handlers::vote::get_voted_polls)
+                    .wrap(from_fn(require_auth)),
             )
             .route(
                 "/api/reset_votes/{poll_id}",
-                web::delete().to(handlers::vote::reset_votes),
+                web::delete()
+                    .to(handlers::vote::reset_votes)
+                    .wrap(from_fn(require_auth)),
             )
             .route(
                 "/api/toggle_poll_status/{poll_id}",
-                web::put().to(handlers::poll::toggle_poll_status),
+                web::put()
+                    .to(handlers::poll::toggle_poll_status)
+                    .wrap(from_fn(require_auth)),
             )
             .route(
                 "/api/votes/{poll_id}/{user_id}",
-                web::get().to(handlers::vote::get_vote_by_poll_and_user),
+                web::get()
+                    .to(handlers::vote::get_vote_by_poll_and_user)
+                    .wrap(from_fn(require_auth)),
+            )
+            .route(
+                "/api/polls/{poll_id}/collaborators",
+                web::put()
+                    .to(handlers::poll::update_collaborator)
+                    .wrap(from_fn(require_auth)),
+            )
+            .route(
+                "/api/users/{user_id}/role",
+                web::put()
+                    .to(handlers::user::update_user_role)
+                    .wrap(from_fn(require_auth)),
+            )
+            .route(
+                "/api/admin/tally_drift",
+                web::get()
+                    .to(handlers::poll::get_tally_drift)
+                    .wrap(from_fn(require_auth)),
+            )
+            .route(
+                "/api/admin/tally_drift/repair",
+                web::post()
+                    .to(handlers::poll::repair_tally_drift)
+                    .wrap(from_fn(require_auth)),
             )
             .route(
                 "/api/poll_results/{poll_id}",
                 web::get().to(handlers::poll::get_poll_results),
             )
+            .route(
+                "/api/poll_results/{poll_id}/irv",
+                web::get().to(handlers::poll::get_irv_results),
+            )
+            .route(
+                "/api/poll_results/{poll_id}/schulze",
+                web::get().to(handlers::poll::get_schulze_results),
+            )
+            .route(
+                "/api/poll_results/{poll_id}/score",
+                web::get().to(handlers::poll::get_score_results),
+            )
     })
     .bind(("0.0.0.0", 3030))?
-    .run()
-    .await
+    .disable_signals()
+    .run();
+
+    // Handle signals ourselves so open websockets and event streams are told
+    // to close before the server waits for connections to finish.
+    let handle = server.handle();
+    actix_web::rt::spawn(async move {
+        wait_for_shutdown_signal().await;
+        realtime::shutdown::begin();
+        handle.stop(true).await;
+    });
+
+    server.await
+}
+
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
 }
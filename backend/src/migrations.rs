@@ -0,0 +1,197 @@
+mod backfill_defaults;
+mod build_tallies;
+mod count_ballots;
+mod create_indexes;
+mod date_poll_creation;
+mod dedupe_users;
+mod dedupe_votes;
+mod expire_pending_logins;
+mod expire_tokens;
+mod unique_user_ids;
+
+use futures::future::BoxFuture;
+use mongodb::bson::{doc, DateTime, Document};
+use mongodb::{Collection, Database};
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::utils::db::_is_duplicate_key_error;
+
+type MigrationFn = for<'a> fn(&'a Database) -> BoxFuture<'a, mongodb::error::Result<()>>;
+
+struct Migration {
+    version: i32,
+    name: &'static str,
+    run: MigrationFn,
+}
+
+// Applied in order, each at most once per database. Append new steps with the
+// next version number; never reorder or edit a step that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "dedupe_votes",
+        run: |db| Box::pin(dedupe_votes::run(db)),
+    },
+    Migration {
+        version: 2,
+        name: "create_indexes",
+        run: |db| Box::pin(create_indexes::run(db)),
+    },
+    Migration {
+        version: 3,
+        name: "backfill_defaults",
+        run: |db| Box::pin(backfill_defaults::run(db)),
+    },
+    Migration {
+        version: 4,
+        name: "build_tallies",
+        run: |db| Box::pin(build_tallies::run(db)),
+    },
+    Migration {
+        version: 5,
+        name: "dedupe_users",
+        run: |db| Box::pin(dedupe_users::run(db)),
+    },
+    Migration {
+        version: 6,
+        name: "unique_user_ids",
+        run: |db| Box::pin(unique_user_ids::run(db)),
+    },
+    Migration {
+        version: 7,
+        name: "count_ballots",
+        run: |db| Box::pin(count_ballots::run(db)),
+    },
+    Migration {
+        version: 8,
+        name: "expire_tokens",
+        run: |db| Box::pin(expire_tokens::run(db)),
+    },
+    Migration {
+        version: 9,
+        name: "expire_pending_logins",
+        run: |db| Box::pin(expire_pending_logins::run(db)),
+    },
+    Migration {
+        version: 10,
+        name: "date_poll_creation",
+        run: |db| Box::pin(date_poll_creation::run(db)),
+    },
+];
+
+// How long a claim protects a migration that hasn't finished. After that it
+// is assumed its instance died mid-step and another instance takes it over.
+const DEFAULT_CLAIM_LEASE_SECS: u64 = 15 * 60;
+
+enum Claim {
+    Acquired(DateTime),
+    Applied,
+    Held(DateTime),
+}
+
+// Applies the migrations this database hasn't seen yet. Each one is claimed
+// in the `migrations` collection before it runs, so instances starting
+// together don't apply the same step twice.
+pub async fn run(db: &Database) -> Result<(), Box<dyn Error>> {
+    let records = db.collection::<Document>("migrations");
+    let lease = env::var("MIGRATION_CLAIM_LEASE_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(DEFAULT_CLAIM_LEASE_SECS));
+
+    for migration in MIGRATIONS {
+        let claimed_at = match claim(&records, migration, lease).await? {
+            Claim::Acquired(claimed_at) => claimed_at,
+            Claim::Applied => continue,
+            Claim::Held(claimed_at) => {
+                return Err(format!(
+                    "Migration {} ({}) was claimed by another instance at {}. If that \
+                     instance is gone, the claim can be taken over once it is {}s old, or \
+                     removed now with db.migrations.deleteOne({{ _id: {} }})",
+                    migration.version,
+                    migration.name,
+                    claimed_at,
+                    lease.as_secs(),
+                    migration.version
+                )
+                .into());
+            }
+        };
+        // Only touch the record while it still holds this instance's claim.
+        let ours = doc! { "_id": migration.version, "claimed_at": claimed_at };
+
+        log::info!("Applying migration {} ({})", migration.version, migration.name);
+        if let Err(e) = (migration.run)(db).await {
+            // Release the claim so the step is retried on the next run.
+            records.delete_one(ours).await?;
+            return Err(format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.name, e
+            )
+            .into());
+        }
+
+        records
+            .update_one(ours, doc! { "$set": { "applied_at": DateTime::now() } })
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn claim(
+    records: &Collection<Document>,
+    migration: &Migration,
+    lease: Duration,
+) -> Result<Claim, Box<dyn Error>> {
+    loop {
+        let now = DateTime::now();
+        let record = doc! {
+            "_id": migration.version,
+            "name": migration.name,
+            "claimed_at": now,
+        };
+        match records.insert_one(record).await {
+            Ok(_) => return Ok(Claim::Acquired(now)),
+            Err(e) if !_is_duplicate_key_error(&e) => return Err(Box::new(e)),
+            Err(_) => {}
+        }
+
+        // Claims recorded before leases existed have no `claimed_at` and are
+        // treated as expired.
+        let expired = DateTime::from_millis(now.timestamp_millis() - lease.as_millis() as i64);
+        let taken = records
+            .update_one(
+                doc! {
+                    "_id": migration.version,
+                    "applied_at": { "$exists": false },
+                    "$or": [
+                        { "claimed_at": { "$lt": expired } },
+                        { "claimed_at": { "$exists": false } },
+                    ],
+                },
+                doc! { "$set": { "claimed_at": now } },
+            )
+            .await?;
+        if taken.modified_count == 1 {
+            log::warn!(
+                "Taking over the expired claim on migration {} ({})",
+                migration.version,
+                migration.name
+            );
+            return Ok(Claim::Acquired(now));
+        }
+
+        match records.find_one(doc! { "_id": migration.version }).await? {
+            Some(record) if record.contains_key("applied_at") => return Ok(Claim::Applied),
+            Some(record) => {
+                return Ok(Claim::Held(record.get_datetime("claimed_at").copied().unwrap_or(now)))
+            }
+            // Released since the insert failed; try to claim it again.
+            None => continue,
+        }
+    }
+}
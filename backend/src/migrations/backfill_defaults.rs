@@ -0,0 +1,36 @@
+use mongodb::bson::{doc, Document};
+use mongodb::Database;
+
+// Writes the serde defaults of fields added after launch into older
+// documents, so queries can filter on them.
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    let polls = db.collection::<Document>("polls");
+    polls
+        .update_many(
+            doc! { "voting_method": { "$exists": false } },
+            doc! { "$set": { "voting_method": "plurality" } },
+        )
+        .await?;
+    polls
+        .update_many(
+            doc! { "collaborators": { "$exists": false } },
+            doc! { "$set": { "collaborators": [] } },
+        )
+        .await?;
+
+    db.collection::<Document>("votes")
+        .update_many(
+            doc! { "scores": { "$exists": false } },
+            doc! { "$set": { "scores": [] } },
+        )
+        .await?;
+
+    db.collection::<Document>("users")
+        .update_many(
+            doc! { "role": { "$exists": false } },
+            doc! { "$set": { "role": "member" } },
+        )
+        .await?;
+
+    Ok(())
+}
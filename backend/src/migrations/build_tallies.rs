@@ -0,0 +1,52 @@
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::Database;
+
+// Builds the per-poll counters from the votes cast before they were
+// maintained incrementally.
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    let pipeline = vec![
+        doc! { "$unwind": "$option_ids" },
+        doc! { "$group": {
+            "_id": { "poll_id": "$poll_id", "option_id": "$option_ids" },
+            "count": { "$sum": 1 },
+        } },
+    ];
+
+    let mut tallies: Vec<(ObjectId, Document)> = Vec::new();
+    let mut cursor = db
+        .collection::<Document>("votes")
+        .aggregate(pipeline)
+        .await?;
+    while let Some(group) = cursor.try_next().await? {
+        let Ok(key) = group.get_document("_id") else {
+            continue;
+        };
+        let (Ok(poll_id), Ok(option_id)) =
+            (key.get_object_id("poll_id"), key.get_object_id("option_id"))
+        else {
+            continue;
+        };
+        let count = group.get_i32("count").unwrap_or(0);
+
+        match tallies.iter_mut().find(|(id, _)| *id == poll_id) {
+            Some((_, counts)) => {
+                counts.insert(option_id.to_hex(), count);
+            }
+            None => tallies.push((poll_id, doc! { option_id.to_hex(): count })),
+        }
+    }
+
+    let collection = db.collection::<Document>("tallies");
+    for (poll_id, counts) in tallies {
+        collection
+            .replace_one(
+                doc! { "_id": poll_id },
+                doc! { "_id": poll_id, "counts": counts },
+            )
+            .upsert(true)
+            .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::{Database, IndexModel};
+
+// Backfills `polls.ballot_count`, which the "most votes" listing sorts on, and
+// indexes it in that order.
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    let polls = db.collection::<Document>("polls");
+
+    let pipeline = vec![doc! { "$group": { "_id": "$poll_id", "count": { "$sum": 1 } } }];
+    let mut cursor = db
+        .collection::<Document>("votes")
+        .aggregate(pipeline)
+        .await?;
+    while let Some(group) = cursor.try_next().await? {
+        let Ok(poll_id) = group.get_object_id("_id") else {
+            continue;
+        };
+        let count = group.get_i32("count").unwrap_or(0);
+        polls
+            .update_one(
+                doc! { "_id": poll_id },
+                doc! { "$set": { "ballot_count": count } },
+            )
+            .await?;
+    }
+
+    polls
+        .update_many(
+            doc! { "ballot_count": { "$exists": false } },
+            doc! { "$set": { "ballot_count": 0 } },
+        )
+        .await?;
+
+    polls
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "ballot_count": -1, "_id": -1 })
+                .build(),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use mongodb::bson::{doc, Document};
+use mongodb::options::IndexOptions;
+use mongodb::{Database, IndexModel};
+
+fn index(keys: Document, unique: bool) -> IndexModel {
+    IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().unique(unique).build())
+        .build()
+}
+
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    // Also serves lookups by `poll_id` alone.
+    db.collection::<Document>("votes")
+        .create_indexes([
+            index(doc! { "poll_id": 1, "user_id": 1 }, true),
+            index(doc! { "user_id": 1 }, false),
+        ])
+        .await?;
+
+    db.collection::<Document>("polls")
+        .create_index(index(doc! { "created_by": 1 }, false))
+        .await?;
+
+    db.collection::<Document>("users")
+        .create_index(index(doc! { "user_id": 1 }, false))
+        .await?;
+
+    db.collection::<Document>("credentials")
+        .create_index(index(doc! { "user_id": 1 }, true))
+        .await?;
+
+    db.collection::<Document>("refresh_tokens")
+        .create_indexes([
+            index(doc! { "token_hash": 1 }, true),
+            index(doc! { "family_id": 1 }, false),
+        ])
+        .await?;
+
+    db.collection::<Document>("revoked_tokens")
+        .create_index(index(doc! { "jti": 1 }, false))
+        .await?;
+
+    Ok(())
+}
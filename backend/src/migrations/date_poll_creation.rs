@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, Document};
+use mongodb::{Database, IndexModel};
+
+// Converts `polls.created_at` from a string to a BSON date, which sorts and
+// compares by time, and indexes it for listings filtered by creation time.
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    let polls = db.collection::<Document>("polls");
+
+    let mut cursor = polls
+        .find(doc! { "created_at": { "$type": "string" } })
+        .projection(doc! { "created_at": 1 })
+        .await?;
+    while let Some(poll) = cursor.try_next().await? {
+        let Ok(id) = poll.get_object_id("_id") else {
+            continue;
+        };
+        // An unreadable time falls back to the one in the poll's id.
+        let created_at = poll
+            .get_str("created_at")
+            .ok()
+            .and_then(|created_at| created_at.parse::<DateTime<Utc>>().ok())
+            .map(bson::DateTime::from_chrono)
+            .unwrap_or_else(|| id.timestamp());
+        polls
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$set": { "created_at": created_at } },
+            )
+            .await?;
+    }
+
+    polls
+        .create_index(IndexModel::builder().keys(doc! { "created_at": -1 }).build())
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::Database;
+
+// Users used to be stored with a find-then-insert, so concurrent logins could
+// store the same user twice. Keeps the oldest record, which everything written
+// so far has been reading, and deletes the rest so `user_id` can be unique.
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    let users = db.collection::<Document>("users");
+
+    let pipeline = vec![
+        doc! { "$sort": { "_id": 1 } },
+        doc! { "$group": {
+            "_id": "$user_id",
+            "ids": { "$push": "$_id" },
+            "count": { "$sum": 1 },
+        } },
+        doc! { "$match": { "count": { "$gt": 1 } } },
+    ];
+
+    let mut duplicates = Vec::new();
+    let mut cursor = users.aggregate(pipeline).await?;
+    while let Some(group) = cursor.try_next().await? {
+        if let Ok(ids) = group.get_array("ids") {
+            duplicates.extend(ids.iter().skip(1).cloned());
+        }
+    }
+
+    if !duplicates.is_empty() {
+        users
+            .delete_many(doc! { "_id": { "$in": Bson::Array(duplicates) } })
+            .await?;
+    }
+
+    Ok(())
+}
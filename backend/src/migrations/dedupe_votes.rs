@@ -0,0 +1,36 @@
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::Database;
+
+// Votes used to be written with a find-then-insert, so a user can have more
+// than one vote on a poll. Keeps the newest and deletes the rest, which the
+// unique vote index requires.
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    let votes = db.collection::<Document>("votes");
+
+    let pipeline = vec![
+        doc! { "$sort": { "_id": -1 } },
+        doc! { "$group": {
+            "_id": { "poll_id": "$poll_id", "user_id": "$user_id" },
+            "ids": { "$push": "$_id" },
+            "count": { "$sum": 1 },
+        } },
+        doc! { "$match": { "count": { "$gt": 1 } } },
+    ];
+
+    let mut duplicates = Vec::new();
+    let mut cursor = votes.aggregate(pipeline).await?;
+    while let Some(group) = cursor.try_next().await? {
+        if let Ok(ids) = group.get_array("ids") {
+            duplicates.extend(ids.iter().skip(1).cloned());
+        }
+    }
+
+    if !duplicates.is_empty() {
+        votes
+            .delete_many(doc! { "_id": { "$in": Bson::Array(duplicates) } })
+            .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use mongodb::bson::{doc, Document};
+use mongodb::options::IndexOptions;
+use mongodb::{Database, IndexModel};
+use std::time::Duration;
+
+// Pending OIDC logins are looked up by `state` and deleted by MongoDB once
+// they expire.
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    let collection = db.collection::<Document>("pending_logins");
+
+    collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "state": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+        )
+        .await?;
+
+    collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "expires_at": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(Duration::from_secs(0))
+                        .build(),
+                )
+                .build(),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, Document};
+use mongodb::options::IndexOptions;
+use mongodb::{Database, IndexModel};
+use std::time::Duration;
+
+// Lets MongoDB delete refresh tokens and revocations once they expire. TTL
+// indexes only act on BSON dates, so expiries stored as strings before the
+// models switched over are converted first.
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    for name in ["refresh_tokens", "revoked_tokens"] {
+        let collection = db.collection::<Document>(name);
+
+        let mut cursor = collection
+            .find(doc! { "expires_at": { "$type": "string" } })
+            .projection(doc! { "expires_at": 1 })
+            .await?;
+        while let Some(token) = cursor.try_next().await? {
+            let (Ok(id), Ok(expires_at)) =
+                (token.get_object_id("_id"), token.get_str("expires_at"))
+            else {
+                continue;
+            };
+            // An unreadable expiry can't be honoured, so the token goes now.
+            let expires_at = expires_at
+                .parse::<DateTime<Utc>>()
+                .unwrap_or_else(|_| Utc::now());
+            collection
+                .update_one(
+                    doc! { "_id": id },
+                    doc! { "$set": { "expires_at": bson::DateTime::from_chrono(expires_at) } },
+                )
+                .await?;
+        }
+
+        collection
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "expires_at": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .expire_after(Duration::from_secs(0))
+                            .build(),
+                    )
+                    .build(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
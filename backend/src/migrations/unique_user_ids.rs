@@ -0,0 +1,30 @@
+use mongodb::bson::{doc, Document};
+use mongodb::error::ErrorKind;
+use mongodb::options::IndexOptions;
+use mongodb::{Database, IndexModel};
+
+// Code for dropping an index that doesn't exist.
+const INDEX_NOT_FOUND: i32 = 27;
+
+// Replaces the plain `users.user_id` index from `create_indexes` with a unique
+// one. Relies on `dedupe_users` having run first.
+pub async fn run(db: &Database) -> mongodb::error::Result<()> {
+    let users = db.collection::<Document>("users");
+
+    if let Err(e) = users.drop_index("user_id_1").await {
+        if !matches!(e.kind.as_ref(), ErrorKind::Command(c) if c.code == INDEX_NOT_FOUND) {
+            return Err(e);
+        }
+    }
+
+    users
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "user_id": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,22 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+// Password credentials are kept apart from `User` so the hash never ends up
+// in a response that serializes a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credential {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: String,
+    pub password_hash: String,
+}
+
+impl Credential {
+    pub fn _new(user_id: String, password_hash: String) -> Self {
+        Credential {
+            id: None,
+            user_id,
+            password_hash,
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub mod user;
 pub mod poll;
-pub mod vote;
\ No newline at end of file
+pub mod vote;
+pub mod credential;
+pub mod token;
\ No newline at end of file
@@ -1,16 +1,79 @@
+use bson::serde_helpers::chrono_datetime_as_bson_datetime;
 use mongodb::bson::{oid::ObjectId, doc};
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
 
-#[derive(Debug, Serialize, Deserialize)]
+// How ballots for a poll are interpreted. Polls stored before this field
+// existed deserialize as `Plurality`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VotingMethod {
+    // Each selected option counts once; `is_multiple_choice` decides how many
+    // options a ballot may select.
+    #[default]
+    Plurality,
+    // `Vote.option_ids` is a ranking, most preferred first.
+    RankedChoice,
+    // `Vote.scores` rates each option within the poll's `score_range`.
+    Score,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl Default for ScoreRange {
+    fn default() -> Self {
+        ScoreRange { min: 0, max: 5 }
+    }
+}
+
+impl ScoreRange {
+    // Results keep a bucket per score, so the range is kept small.
+    pub const MAX_SPAN: i64 = 100;
+
+    pub fn is_valid(&self) -> bool {
+        self.min < self.max && i64::from(self.max) - i64::from(self.min) <= Self::MAX_SPAN
+    }
+}
+
+// A collaborator's role on a single poll. The poll's creator is always an
+// owner without being listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PollRole {
+    // Manages the poll, its votes and its collaborators.
+    Owner,
+    // Opens and closes the poll.
+    Editor,
+    // Listed on the poll without management rights.
+    Viewer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Poll {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub question: String,
     pub options: Vec<(ObjectId, String)>,
     pub created_by: String,
+    #[serde(default)]
+    pub collaborators: Vec<(String, PollRole)>,
+    // A BSON date, so listings can filter on it.
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
     pub created_at: chrono::DateTime<Utc>,
     pub is_multiple_choice: bool,
+    #[serde(default)]
+    pub voting_method: VotingMethod,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_range: Option<ScoreRange>,
+    // Pending scheduled status changes; each is cleared once applied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opens_at: Option<chrono::DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closes_at: Option<chrono::DateTime<Utc>>,
     pub isactive: bool,
 }
 
@@ -20,6 +83,8 @@ impl Poll {
         options: Vec<String>,
         created_by: String,
         is_multiple_choice: bool,
+        voting_method: VotingMethod,
+        score_range: Option<ScoreRange>,
     ) -> Self {
         let options_with_ids = options.into_iter()
             .map(|text| (ObjectId::new(), text))
@@ -30,9 +95,71 @@ impl Poll {
             question,
             options: options_with_ids,
             created_by,
+            collaborators: Vec::new(),
             created_at: Utc::now(),
             is_multiple_choice,
+            voting_method,
+            score_range,
+            opens_at: None,
+            closes_at: None,
             isactive: true,
         }
     }
+
+    // Schedules the poll to open and/or close automatically. A poll that
+    // opens in the future starts out inactive.
+    pub fn with_schedule(
+        mut self,
+        opens_at: Option<chrono::DateTime<Utc>>,
+        closes_at: Option<chrono::DateTime<Utc>>,
+    ) -> Self {
+        self.isactive = opens_at.is_none_or(|opens_at| opens_at <= Utc::now());
+        self.opens_at = opens_at;
+        self.closes_at = closes_at;
+        self
+    }
+
+    // Whether the schedule allows voting at `now`, regardless of `isactive`.
+    pub fn is_within_schedule(&self, now: chrono::DateTime<Utc>) -> bool {
+        self.opens_at.is_none_or(|opens_at| opens_at <= now)
+            && self.closes_at.is_none_or(|closes_at| now < closes_at)
+    }
+}
+
+// What anyone may see of a poll: everything but its collaborators.
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicPoll {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub question: String,
+    pub options: Vec<(ObjectId, String)>,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<Utc>,
+    pub is_multiple_choice: bool,
+    pub voting_method: VotingMethod,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score_range: Option<ScoreRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opens_at: Option<chrono::DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closes_at: Option<chrono::DateTime<Utc>>,
+    pub isactive: bool,
+}
+
+impl From<Poll> for PublicPoll {
+    fn from(poll: Poll) -> Self {
+        PublicPoll {
+            id: poll.id,
+            question: poll.question,
+            options: poll.options,
+            created_by: poll.created_by,
+            created_at: poll.created_at,
+            is_multiple_choice: poll.is_multiple_choice,
+            voting_method: poll.voting_method,
+            score_range: poll.score_range,
+            opens_at: poll.opens_at,
+            closes_at: poll.closes_at,
+            isactive: poll.isactive,
+        }
+    }
 }
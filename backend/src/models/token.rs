@@ -0,0 +1,92 @@
+use bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+// A refresh token is stored only as a hash. Every token issued from the same
+// login shares a `family_id`, so presenting an already used token revokes
+// the whole family. `expires_at` is a BSON date so MongoDB can expire it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub token_hash: String,
+    pub family_id: String,
+    pub user_id: String,
+    pub created_at: chrono::DateTime<Utc>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: chrono::DateTime<Utc>,
+    pub used: bool,
+    pub revoked: bool,
+}
+
+impl RefreshToken {
+    pub fn _new(
+        token_hash: String,
+        family_id: String,
+        user_id: String,
+        expires_at: chrono::DateTime<Utc>,
+    ) -> Self {
+        RefreshToken {
+            id: None,
+            token_hash,
+            family_id,
+            user_id,
+            created_at: Utc::now(),
+            expires_at,
+            used: false,
+            revoked: false,
+        }
+    }
+}
+
+// An access token revoked before its expiry, identified by its `jti` claim.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevokedToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub jti: String,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: chrono::DateTime<Utc>,
+}
+
+impl RevokedToken {
+    pub fn _new(jti: String, expires_at: chrono::DateTime<Utc>) -> Self {
+        RevokedToken {
+            id: None,
+            jti,
+            expires_at,
+        }
+    }
+}
+
+// An OIDC login between the redirect to the provider and the callback, found
+// by its `state` parameter. Stored rather than held in memory so the callback
+// can reach any replica.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingLogin {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub state: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: chrono::DateTime<Utc>,
+}
+
+impl PendingLogin {
+    pub fn _new(
+        state: String,
+        code_verifier: String,
+        nonce: String,
+        expires_at: chrono::DateTime<Utc>,
+    ) -> Self {
+        PendingLogin {
+            id: None,
+            state,
+            code_verifier,
+            nonce,
+            expires_at,
+        }
+    }
+}
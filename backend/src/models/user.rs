@@ -1,12 +1,26 @@
 use mongodb::bson::{doc, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+// Site-wide role. Users stored before roles existed are members.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    // Can moderate every poll and assign roles.
+    Admin,
+    #[default]
+    Member,
+    // Read-only: cannot create polls or vote.
+    Viewer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub user_id: String,
     pub name: String,
+    #[serde(default)]
+    pub role: Role,
 }
 
 impl User {
@@ -15,6 +29,7 @@ impl User {
             id: None,
             user_id,
             name,
+            role: Role::default(),
         }
     }
 }
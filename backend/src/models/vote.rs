@@ -1,12 +1,15 @@
 use mongodb::bson::{oid::ObjectId, doc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vote {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub poll_id: ObjectId,
     pub option_ids: Vec<ObjectId>,
+    // `(option_id, score)` pairs, only present on ballots for score polls.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scores: Vec<(ObjectId, i32)>,
     pub user_id: String,
 }
 
@@ -16,6 +19,7 @@ impl Vote {
             id: None,
             poll_id,
             option_ids,
+            scores: Vec::new(),
             user_id,
         }
     }
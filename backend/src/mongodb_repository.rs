@@ -1,37 +1,75 @@
-use crate::models::user::User;
-use crate::models::{poll::Poll, vote::Vote};
-use crate::repositories::{PollRepository, Repository, UserRepository, VoteRepository};
+use crate::models::credential::Credential;
+use crate::models::token::{PendingLogin, RefreshToken, RevokedToken};
+use crate::models::user::{Role, User};
+use crate::models::{poll::{Poll, PollRole}, vote::Vote};
+use crate::repositories::{AccountExists, LeaseRepository, PollRepository, Repository, TallyCheck, TokenRepository, UserRepository, VoteConflict, VoteRepository};
 use async_trait::async_trait;
 use futures::TryStreamExt;
-use mongodb::bson::{doc, oid::ObjectId};
-use mongodb::{Client, Collection};
+use crate::repositories::poll_query::{PollCursor, PollPage, PollQuery, PollSort, PollStatusFilter};
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson, to_document, Bson, DateTime, Document};
+use mongodb::error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT};
+use mongodb::options::{ReadConcern, ReturnDocument};
+use mongodb::{Client, ClientSession, Collection, Database};
+use crate::utils::db::_is_duplicate_key_error;
 use std::error::Error;
+use std::time::Duration;
 
 pub struct MongoDBRepository {
+    client: Client,
+    database: Database,
     poll_collection: Collection<Poll>,
     vote_collection: Collection<Vote>,
+    // One document per poll: `{ _id: poll_id, counts: { <option_id>: n } }`.
+    tally_collection: Collection<Document>,
     user_collection: Collection<User>,
+    credential_collection: Collection<Credential>,
+    refresh_token_collection: Collection<RefreshToken>,
+    revoked_token_collection: Collection<RevokedToken>,
+    pending_login_collection: Collection<PendingLogin>,
+    // One document per lease: `{ _id: name, holder, expires_at }`.
+    lease_collection: Collection<Document>,
+    // False on a standalone server, which has no multi-document transactions.
+    // Votes and their counters are then written one after the other, and a
+    // write interrupted in between leaves drift for reconciliation to repair.
+    transactions: bool,
 }
 
 impl MongoDBRepository {
-    pub fn new(client: &Client) -> Self {
+    pub fn new(client: &Client, transactions: bool) -> Self {
         let db = client.database("polling_app");
         MongoDBRepository {
+            client: client.clone(),
+            database: db.clone(),
             poll_collection: db.collection::<Poll>("polls"),
             vote_collection: db.collection::<Vote>("votes"),
+            tally_collection: db.collection::<Document>("tallies"),
             user_collection: db.collection::<User>("users"),
-
+            credential_collection: db.collection::<Credential>("credentials"),
+            refresh_token_collection: db.collection::<RefreshToken>("refresh_tokens"),
+            revoked_token_collection: db.collection::<RevokedToken>("revoked_tokens"),
+            pending_login_collection: db.collection::<PendingLogin>("pending_logins"),
+            lease_collection: db.collection::<Document>("leases"),
+            transactions,
         }
     }
+
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
 }
 
 #[async_trait]
 impl UserRepository for MongoDBRepository {
     async fn store_user(&self, user: User) -> Result<(), Box<dyn Error>> {
-        let filter = doc! { "user_id": &user.user_id };
-        if self.user_collection.find_one(filter).await?.is_none() {
-            self.user_collection.insert_one(user).await?;
-        }
+        // Upserting leaves an existing user untouched and, unlike a lookup
+        // followed by an insert, cannot create the same user twice.
+        self.user_collection
+            .update_one(
+                doc! { "user_id": &user.user_id },
+                doc! { "$setOnInsert": to_document(&user)? },
+            )
+            .upsert(true)
+            .await?;
         Ok(())
     }
 
@@ -40,12 +78,65 @@ impl UserRepository for MongoDBRepository {
         let user = self.user_collection.find_one(filter).await?;
         Ok(user)
     }
+
+    async fn find_users_by_ids(&self, user_ids: Vec<String>) -> Result<Vec<User>, Box<dyn Error>> {
+        let filter = doc! { "user_id": { "$in": user_ids } };
+        let cursor = self.user_collection.find(filter).await?;
+        let users: Vec<User> = cursor.try_collect().await?;
+        Ok(users)
+    }
+
+    async fn update_user_role(&self, user_id: &str, role: Role) -> Result<bool, Box<dyn Error>> {
+        let result = self
+            .user_collection
+            .update_one(
+                doc! { "user_id": user_id },
+                doc! { "$set": { "role": to_bson(&role)? } },
+            )
+            .await?;
+        Ok(result.matched_count == 1)
+    }
+
+    async fn create_account(&self, user: User, credential: Credential) -> Result<(), Box<dyn Error>> {
+        // The unique index on `credentials.user_id` picks a single winner among
+        // concurrent registrations; only the winner goes on to write the user.
+        if let Err(e) = self.credential_collection.insert_one(&credential).await {
+            if _is_duplicate_key_error(&e) {
+                return Err(Box::new(AccountExists));
+            }
+            return Err(e.into());
+        }
+
+        // A user without a credential predates passwords and isn't up for
+        // grabs; the unique index on `users.user_id` refuses it.
+        let inserted = self.user_collection.insert_one(&user).await;
+        let failure: Box<dyn Error + Send + Sync> = match inserted {
+            Ok(_) => return Ok(()),
+            Err(e) if _is_duplicate_key_error(&e) => Box::new(AccountExists),
+            Err(e) => e.into(),
+        };
+
+        // Release the user ID so the registration can be retried.
+        let _ = self
+            .credential_collection
+            .delete_one(doc! { "user_id": &credential.user_id })
+            .await;
+        Err(failure)
+    }
+
+    async fn find_credential(&self, user_id: &str) -> Result<Option<Credential>, Box<dyn Error>> {
+        let filter = doc! { "user_id": user_id };
+        let credential = self.credential_collection.find_one(filter).await?;
+        Ok(credential)
+    }
 }
 
 #[async_trait]
 impl PollRepository for MongoDBRepository {
     async fn create_poll(&self, poll: Poll) -> Result<(), Box<dyn Error>> {
-        self.poll_collection.insert_one(poll).await?;
+        let mut document = to_document(&poll)?;
+        document.insert("ballot_count", 0);
+        self.poll_collection.clone_with_type::<Document>().insert_one(document).await?;
         Ok(())
     }
 
@@ -64,57 +155,428 @@ impl PollRepository for MongoDBRepository {
         Ok(polls)
     }
 
-    async fn get_all_polls(&self) -> Result<Vec<Poll>, Box<dyn Error>> {
-        let cursor = self.poll_collection.find(doc! {}).await?;
-        let polls: Vec<Poll> = cursor.try_collect().await?;
-        Ok(polls)
+    async fn list_polls(&self, query: &PollQuery) -> Result<PollPage, Box<dyn Error>> {
+        let mut conditions: Vec<Document> = Vec::new();
+        match query.status {
+            Some(PollStatusFilter::Active) => conditions.push(doc! { "isactive": true }),
+            Some(PollStatusFilter::Closed) => conditions.push(doc! { "isactive": false }),
+            None => {}
+        }
+        if let Some(user_id) = &query.created_by {
+            conditions.push(doc! { "created_by": user_id });
+        }
+        if let Some(after) = query.created_after {
+            conditions.push(doc! { "created_at": { "$gte": DateTime::from_chrono(after) } });
+        }
+        if let Some(before) = query.created_before {
+            conditions.push(doc! { "created_at": { "$lt": DateTime::from_chrono(before) } });
+        }
+        if let Some((user_id, has_voted)) = &query.voted_by {
+            let voted_poll_ids = self
+                .vote_collection
+                .distinct("poll_id", doc! { "user_id": user_id })
+                .await?;
+            let operator = if *has_voted { "$in" } else { "$nin" };
+            conditions.push(doc! { "_id": { operator: voted_poll_ids } });
+        }
+
+        let mut pipeline = Vec::new();
+        if !conditions.is_empty() {
+            pipeline.push(doc! { "$match": { "$and": conditions } });
+        }
+
+        let sort = match query.sort {
+            PollSort::Newest => doc! { "_id": -1 },
+            // Kept up to date as votes are written, see `write_vote`.
+            PollSort::MostVotes => doc! { "ballot_count": -1, "_id": -1 },
+            PollSort::ClosingSoonest => {
+                // Stored timestamps order correctly as strings, to the second.
+                pipeline.push(doc! {
+                    "$set": {
+                        "_open_ended": { "$ne": [{ "$type": "$closes_at" }, "string"] },
+                    }
+                });
+                doc! { "_open_ended": 1, "closes_at": 1, "_id": -1 }
+            }
+        };
+
+        if let Some(after) = &query.after {
+            let position = match after {
+                PollCursor::Newest { id } => doc! { "_id": { "$lt": id } },
+                PollCursor::MostVotes { votes, id } => doc! {
+                    "$or": [
+                        { "ballot_count": { "$lt": votes } },
+                        { "ballot_count": votes, "_id": { "$lt": id } },
+                    ]
+                },
+                PollCursor::ClosingSoonest { closes_at: Some(closes_at), id } => doc! {
+                    "$or": [
+                        { "_open_ended": false, "closes_at": { "$gt": closes_at } },
+                        { "_open_ended": false, "closes_at": closes_at, "_id": { "$lt": id } },
+                        { "_open_ended": true },
+                    ]
+                },
+                PollCursor::ClosingSoonest { closes_at: None, id } => {
+                    doc! { "_open_ended": true, "_id": { "$lt": id } }
+                }
+            };
+            pipeline.push(doc! { "$match": position });
+        }
+
+        pipeline.push(doc! { "$sort": sort });
+        pipeline.push(doc! { "$limit": (query.limit + 1) as i64 });
+
+        let documents: Vec<Document> =
+            self.poll_collection.aggregate(pipeline).await?.try_collect().await?;
+
+        let mut page = Vec::with_capacity(documents.len());
+        for document in documents {
+            let vote_count = match document.get("ballot_count") {
+                Some(Bson::Int32(n)) => *n as i64,
+                Some(Bson::Int64(n)) => *n,
+                _ => 0,
+            };
+            let poll: Poll = from_document(document)?;
+            page.push((PollCursor::at(&poll, query.sort, vote_count), poll, vote_count));
+        }
+
+        let has_more = page.len() > query.limit;
+        page.truncate(query.limit);
+
+        Ok(PollPage {
+            next_cursor: if has_more {
+                page.last().map(|(cursor, _, _)| cursor.clone())
+            } else {
+                None
+            },
+            ballot_counts: page
+                .iter()
+                .filter_map(|(_, poll, vote_count)| poll.id.map(|id| (id, *vote_count)))
+                .collect(),
+            polls: page.into_iter().map(|(_, poll, _)| poll).collect(),
+        })
     }
 
-    async fn get_polls_by_user(&self, user_id: &str) -> Result<Vec<Poll>, Box<dyn Error>> {
-        let cursor = self.poll_collection.find(doc! { "created_by": user_id }).await?;
+    async fn update_poll_status(&self, id: ObjectId, is_active: bool) -> Result<(), Box<dyn Error>> {
+        let cleared = if is_active {
+            doc! { "opens_at": "" }
+        } else {
+            doc! { "opens_at": "", "closes_at": "" }
+        };
+
+        self.poll_collection
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$set": { "isactive": is_active }, "$unset": cleared },
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn apply_poll_schedule(&self, poll: &Poll, is_active: bool) -> Result<bool, Box<dyn Error>> {
+        let Some(id) = poll.id else { return Ok(false) };
+        let cleared = if is_active {
+            doc! { "opens_at": "" }
+        } else {
+            doc! { "opens_at": "", "closes_at": "" }
+        };
+
+        // A null matches a missing field, which is how an unset time is stored.
+        let filter = doc! {
+            "_id": id,
+            "isactive": poll.isactive,
+            "opens_at": to_bson(&poll.opens_at)?,
+            "closes_at": to_bson(&poll.closes_at)?,
+        };
+        let result = self
+            .poll_collection
+            .update_one(filter, doc! { "$set": { "isactive": is_active }, "$unset": cleared })
+            .await?;
+        Ok(result.matched_count == 1)
+    }
+
+    async fn get_scheduled_polls(&self) -> Result<Vec<Poll>, Box<dyn Error>> {
+        let filter = doc! {
+            "$or": [
+                { "opens_at": { "$ne": null } },
+                { "closes_at": { "$ne": null } },
+            ]
+        };
+        let cursor = self.poll_collection.find(filter).await?;
         let polls: Vec<Poll> = cursor.try_collect().await?;
         Ok(polls)
     }
 
-    async fn update_poll_status(&self, id: ObjectId, is_active: bool) -> Result<(), Box<dyn Error>> {
+    async fn update_poll_collaborators(
+        &self,
+        id: ObjectId,
+        collaborators: Vec<(String, PollRole)>,
+    ) -> Result<(), Box<dyn Error>> {
         self.poll_collection
             .update_one(
                 doc! { "_id": id },
-                doc! { "$set": { "isactive": is_active } },
+                doc! { "$set": { "collaborators": to_bson(&collaborators)? } },
             )
             .await?;
         Ok(())
     }
 
     async fn reset_votes_for_poll(&self, poll_id: ObjectId) -> Result<(), Box<dyn Error>> {
+        let mut session = self.client.start_session().await?;
+        if !self.transactions {
+            return Ok(self.clear_votes(&mut session, poll_id).await?);
+        }
+
+        for attempt in 1..=MAX_TRANSACTION_ATTEMPTS {
+            session.start_transaction().await?;
+            let result = match self.clear_votes(&mut session, poll_id).await {
+                Ok(()) => commit_with_retry(&mut session).await,
+                Err(e) => {
+                    let _ = session.abort_transaction().await;
+                    Err(e)
+                }
+            };
+
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) if is_transient(&e) && attempt < MAX_TRANSACTION_ATTEMPTS => continue,
+                Err(e) => return Err(Box::new(e)),
+            }
+        }
+        unreachable!()
+    }
+
+    async fn get_poll_results(&self, poll_id: ObjectId) -> Result<Vec<(ObjectId, i32)>, Box<dyn Error>> {
+        let tally = self.tally_collection.find_one(doc! { "_id": poll_id }).await?;
+        Ok(tally.map(|tally| read_counts(&tally)).unwrap_or_default())
+    }
+
+    async fn reconcile_poll_results(&self, poll_id: ObjectId, repair: bool) -> Result<TallyCheck, Box<dyn Error>> {
+        let mut session = self.client.start_session().await?;
+        if !self.transactions {
+            // Without a snapshot a vote landing meanwhile can be miscounted;
+            // the next reconciliation corrects it.
+            return Ok(self.check_tally(&mut session, poll_id, repair).await?);
+        }
+
+        for attempt in 1..=MAX_TRANSACTION_ATTEMPTS {
+            // A snapshot so the ballots and counters are read at the same
+            // point; a vote landing meanwhile makes a repair conflict and
+            // retry instead of overwriting its increment.
+            session
+                .start_transaction()
+                .read_concern(ReadConcern::snapshot())
+                .await?;
+            let result = match self.check_tally(&mut session, poll_id, repair).await {
+                Ok(check) => commit_with_retry(&mut session).await.map(|_| check),
+                Err(e) => {
+                    let _ = session.abort_transaction().await;
+                    Err(e)
+                }
+            };
+
+            match result {
+                Ok(check) => return Ok(check),
+                Err(e) if is_transient(&e) && attempt < MAX_TRANSACTION_ATTEMPTS => continue,
+                Err(e) => return Err(Box::new(e)),
+            }
+        }
+        unreachable!()
+    }
+}
+
+impl MongoDBRepository {
+    async fn clear_votes(&self, session: &mut ClientSession, poll_id: ObjectId) -> mongodb::error::Result<()> {
         self.vote_collection
             .delete_many(doc! { "poll_id": poll_id })
+            .session(&mut *session)
+            .await?;
+        self.tally_collection
+            .delete_one(doc! { "_id": poll_id })
+            .session(&mut *session)
+            .await?;
+        // Lets change stream watchers tell which poll was reset.
+        self.poll_collection
+            .update_one(
+                doc! { "_id": poll_id },
+                doc! { "$set": { "votes_reset_at": DateTime::now(), "ballot_count": 0 } },
+            )
+            .session(&mut *session)
             .await?;
         Ok(())
     }
 
-    async fn get_poll_results(&self, poll_id: ObjectId) -> Result<Vec<(ObjectId, i32)>, Box<dyn Error>> {
+    async fn write_vote(&self, session: &mut ClientSession, vote: &Vote) -> mongodb::error::Result<()> {
+        let filter = doc! {
+            "poll_id": vote.poll_id,
+            "user_id": vote.user_id.clone()
+        };
+
+        // A single upsert, so concurrent submissions from one user can't both
+        // insert. The unique vote index (see `migrations::create_indexes`)
+        // turns any race the server doesn't retry into a duplicate key error.
+        let previous = self.vote_collection
+            .find_one_and_update(
+                filter,
+                doc! { "$set": {
+                    "option_ids": vote.option_ids.clone(),
+                    "scores": to_bson(&vote.scores)?,
+                } },
+            )
+            .upsert(true)
+            .return_document(ReturnDocument::Before)
+            .session(&mut *session)
+            .await?;
+
+        let first_ballot = previous.is_none();
+        let previous_ids = previous.map(|previous| previous.option_ids).unwrap_or_default();
+        let changes = count_changes(&previous_ids, &vote.option_ids);
+        if !changes.is_empty() {
+            self.tally_collection
+                .update_one(doc! { "_id": vote.poll_id }, doc! { "$inc": changes })
+                .upsert(true)
+                .session(&mut *session)
+                .await?;
+        }
+        if first_ballot {
+            self.poll_collection
+                .update_one(doc! { "_id": vote.poll_id }, doc! { "$inc": { "ballot_count": 1 } })
+                .session(&mut *session)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn check_tally(&self, session: &mut ClientSession, poll_id: ObjectId, repair: bool) -> mongodb::error::Result<TallyCheck> {
+        let stored = self
+            .tally_collection
+            .find_one(doc! { "_id": poll_id })
+            .session(&mut *session)
+            .await?
+            .map(|tally| read_counts(&tally))
+            .unwrap_or_default();
+
         let pipeline = vec![
             doc! { "$match": { "poll_id": poll_id } },
             doc! { "$unwind": "$option_ids" },
             doc! { "$group": { "_id": "$option_ids", "count": { "$sum": 1 } } },
         ];
+        let mut cursor = self.vote_collection.aggregate(pipeline).session(&mut *session).await?;
+        let mut recounted = Vec::new();
+        while let Some(doc) = cursor.next(&mut *session).await.transpose()? {
+            if let Ok(option_id) = doc.get_object_id("_id") {
+                recounted.push((option_id, doc.get_i32("count").unwrap_or(0)));
+            }
+        }
+
+        if repair && !same_counts(&stored, &recounted) {
+            let mut counts = Document::new();
+            for (option_id, count) in &recounted {
+                counts.insert(option_id.to_hex(), *count);
+            }
+            self.tally_collection
+                .replace_one(doc! { "_id": poll_id }, doc! { "_id": poll_id, "counts": counts })
+                .upsert(true)
+                .session(&mut *session)
+                .await?;
+        }
+        if repair {
+            let ballots = self
+                .vote_collection
+                .count_documents(doc! { "poll_id": poll_id })
+                .session(&mut *session)
+                .await? as i64;
+            self.poll_collection
+                .update_one(
+                    doc! { "_id": poll_id, "ballot_count": { "$ne": ballots } },
+                    doc! { "$set": { "ballot_count": ballots } },
+                )
+                .session(&mut *session)
+                .await?;
+        }
+
+        Ok(TallyCheck { stored, recounted })
+    }
+}
+
+// Transactions that hit a write conflict are retried this many times in all.
+const MAX_TRANSACTION_ATTEMPTS: u32 = 5;
+const COMMIT_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+fn is_transient(error: &mongodb::error::Error) -> bool {
+    error.contains_label(TRANSIENT_TRANSACTION_ERROR)
+}
+
+// Commits whose outcome is unknown, e.g. across a primary election, are safe
+// to retry; they are retried with backoff up to the same limit.
+async fn commit_with_retry(session: &mut ClientSession) -> mongodb::error::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match session.commit_transaction().await {
+            Err(e)
+                if e.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT)
+                    && attempt < MAX_TRANSACTION_ATTEMPTS =>
+            {
+                tokio::time::sleep(COMMIT_RETRY_DELAY * 2u32.pow(attempt - 1)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Non-zero counters of a tally document.
+fn read_counts(tally: &Document) -> Vec<(ObjectId, i32)> {
+    let Ok(counts) = tally.get_document("counts") else {
+        return Vec::new();
+    };
 
-        let mut cursor = self.vote_collection.aggregate(pipeline).await?;
-        let mut results = Vec::new();
+    counts
+        .iter()
+        .filter_map(|(option_id, count)| {
+            let option_id = ObjectId::parse_str(option_id).ok()?;
+            let count = count.as_i32().or_else(|| count.as_i64().map(|count| count as i32))?;
+            (count != 0).then_some((option_id, count))
+        })
+        .collect()
+}
 
-        while let Some(doc) = cursor.try_next().await? {
-            let option_id = doc.get_object_id("_id")?;
-            let count = doc.get_i32("count").unwrap_or(0);
-            results.push((option_id, count));
+// The `$inc` moving one ballot's counts from `previous` to `current`.
+fn count_changes(previous: &[ObjectId], current: &[ObjectId]) -> Document {
+    let mut changes: Vec<(ObjectId, i32)> = Vec::new();
+    let mut add = |option_id: &ObjectId, delta: i32| {
+        match changes.iter_mut().find(|(id, _)| id == option_id) {
+            Some((_, total)) => *total += delta,
+            None => changes.push((*option_id, delta)),
         }
+    };
+    previous.iter().for_each(|option_id| add(option_id, -1));
+    current.iter().for_each(|option_id| add(option_id, 1));
 
-        Ok(results)
+    let mut inc = Document::new();
+    for (option_id, delta) in changes.into_iter().filter(|(_, delta)| *delta != 0) {
+        inc.insert(format!("counts.{}", option_id.to_hex()), delta);
     }
+    inc
+}
+
+fn same_counts(a: &[(ObjectId, i32)], b: &[(ObjectId, i32)]) -> bool {
+    a.len() == b.len() && a.iter().all(|entry| b.contains(entry))
 }
 
 #[async_trait]
 impl VoteRepository for MongoDBRepository {
+    // Find which of the given polls the user has voted in
+    async fn find_voted_poll_ids(
+        &self,
+        user_id: &str,
+        poll_ids: Vec<ObjectId>,
+    ) -> Result<Vec<ObjectId>, Box<dyn Error>> {
+        let filter = doc! { "user_id": user_id, "poll_id": { "$in": poll_ids } };
+        let poll_ids = self.vote_collection.distinct("poll_id", filter).await?;
+        Ok(poll_ids.into_iter().filter_map(|id| id.as_object_id()).collect())
+    }
+
     // Find votes by user
     async fn find_votes_by_user(&self, user_id: &str) -> Result<Vec<Vote>, Box<dyn Error>> {
         let cursor = self.vote_collection.find(doc! { "user_id": user_id }).await?;
@@ -133,40 +595,168 @@ impl VoteRepository for MongoDBRepository {
         Ok(vote)
     }
 
-    // Submit or update a vote
+    // Find every ballot cast in a poll
+    async fn find_votes_by_poll(&self, poll_id: ObjectId) -> Result<Vec<Vote>, Box<dyn Error>> {
+        let cursor = self.vote_collection.find(doc! { "poll_id": poll_id }).await?;
+        let votes: Vec<Vote> = cursor.try_collect().await?;
+        Ok(votes)
+    }
+
+    // Submit or update a vote. The vote and its counters change in one
+    // transaction, which needs MongoDB running as a replica set.
     async fn submit_or_update_vote(&self, vote: Vote) -> Result<(), Box<dyn Error>> {
-        let filter = doc! {
-            "poll_id": vote.poll_id,
-            "user_id": vote.user_id.clone()
-        };
-    
-        // Perform update operation
-        let existing_vote = self.vote_collection.find_one(filter.clone()).await?;
-    
-        if let Some(_) = existing_vote {
-            // Update existing vote
-            let update_result = self.vote_collection
-                .update_one(
-                    filter,
-                    doc! { "$set": { "option_ids": vote.option_ids } },
-                )
-                .await;
-    
-            match update_result {
-                Ok(_) => Ok(()),
-                Err(e) => Err(Box::new(e)),
-            }
-        } else {
-            // Insert new vote
-            let insert_result = self.vote_collection.insert_one(vote).await;
-    
-            match insert_result {
-                Ok(_) => Ok(()),
-                Err(e) => Err(Box::new(e)),
+        let mut session = self.client.start_session().await?;
+        if !self.transactions {
+            return match self.write_vote(&mut session, &vote).await {
+                Err(e) if _is_duplicate_key_error(&e) => Err(Box::new(VoteConflict)),
+                result => Ok(result?),
+            };
+        }
+
+        for attempt in 1..=MAX_TRANSACTION_ATTEMPTS {
+            session.start_transaction().await?;
+            let result = match self.write_vote(&mut session, &vote).await {
+                Ok(()) => commit_with_retry(&mut session).await,
+                Err(e) => {
+                    let _ = session.abort_transaction().await;
+                    Err(e)
+                }
+            };
+
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) if _is_duplicate_key_error(&e) => return Err(Box::new(VoteConflict)),
+                Err(e) if is_transient(&e) && attempt < MAX_TRANSACTION_ATTEMPTS => continue,
+                Err(e) => return Err(Box::new(e)),
             }
         }
+        unreachable!()
     }
 }
 
 #[async_trait]
-impl Repository for MongoDBRepository {}
\ No newline at end of file
+impl TokenRepository for MongoDBRepository {
+    async fn store_refresh_token(&self, token: RefreshToken) -> Result<(), Box<dyn Error>> {
+        self.refresh_token_collection.insert_one(token).await?;
+        Ok(())
+    }
+
+    async fn find_refresh_token(&self, token_hash: &str) -> Result<Option<RefreshToken>, Box<dyn Error>> {
+        let token = self
+            .refresh_token_collection
+            .find_one(doc! { "token_hash": token_hash })
+            .await?;
+        Ok(token)
+    }
+
+    async fn mark_refresh_token_used(&self, token_hash: &str) -> Result<bool, Box<dyn Error>> {
+        let result = self
+            .refresh_token_collection
+            .update_one(
+                doc! { "token_hash": token_hash, "used": false },
+                doc! { "$set": { "used": true } },
+            )
+            .await?;
+        Ok(result.modified_count == 1)
+    }
+
+    async fn revoke_token_family(&self, family_id: &str) -> Result<(), Box<dyn Error>> {
+        self.refresh_token_collection
+            .update_many(
+                doc! { "family_id": family_id },
+                doc! { "$set": { "revoked": true } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn revoke_access_token(&self, token: RevokedToken) -> Result<(), Box<dyn Error>> {
+        self.revoked_token_collection.insert_one(token).await?;
+        Ok(())
+    }
+
+    async fn is_access_token_revoked(&self, jti: &str) -> Result<bool, Box<dyn Error>> {
+        let revoked = self
+            .revoked_token_collection
+            .find_one(doc! { "jti": jti })
+            .await?;
+        Ok(revoked.is_some())
+    }
+
+    async fn store_pending_login(&self, login: PendingLogin) -> Result<(), Box<dyn Error>> {
+        self.pending_login_collection.insert_one(login).await?;
+        Ok(())
+    }
+
+    async fn take_pending_login(&self, state: &str) -> Result<Option<PendingLogin>, Box<dyn Error>> {
+        // The TTL monitor only runs about once a minute, so expiry is checked too.
+        let login = self
+            .pending_login_collection
+            .find_one_and_delete(doc! { "state": state, "expires_at": { "$gt": DateTime::now() } })
+            .await?;
+        Ok(login)
+    }
+}
+
+#[async_trait]
+impl LeaseRepository for MongoDBRepository {
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: Duration) -> Result<bool, Box<dyn Error>> {
+        let now = DateTime::now();
+        let expires_at = DateTime::from_millis(now.timestamp_millis() + ttl.as_millis() as i64);
+
+        // Matches only a lease this holder has or one that expired; otherwise
+        // the upsert collides with the existing `_id` and nothing changes.
+        let result = self
+            .lease_collection
+            .update_one(
+                doc! {
+                    "_id": name,
+                    "$or": [{ "holder": holder }, { "expires_at": { "$lte": now } }],
+                },
+                doc! { "$set": { "holder": holder, "expires_at": expires_at } },
+            )
+            .upsert(true)
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) if _is_duplicate_key_error(&e) => Ok(false),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+}
+
+#[async_trait]
+impl Repository for MongoDBRepository {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_new_ballot_increments_its_options() {
+        let (a, b) = (ObjectId::new(), ObjectId::new());
+        let changes = count_changes(&[], &[a, b]);
+
+        assert_eq!(changes.get_i32(format!("counts.{}", a.to_hex())), Ok(1));
+        assert_eq!(changes.get_i32(format!("counts.{}", b.to_hex())), Ok(1));
+    }
+
+    #[test]
+    fn a_changed_ballot_moves_its_counts() {
+        let (a, b, c) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let changes = count_changes(&[a, b], &[b, c]);
+
+        assert_eq!(changes.get_i32(format!("counts.{}", a.to_hex())), Ok(-1));
+        assert_eq!(changes.get_i32(format!("counts.{}", c.to_hex())), Ok(1));
+        // Kept on both ballots, so left alone.
+        assert!(!changes.contains_key(format!("counts.{}", b.to_hex())));
+    }
+
+    #[test]
+    fn an_unchanged_ballot_changes_nothing() {
+        let (a, b) = (ObjectId::new(), ObjectId::new());
+
+        assert!(count_changes(&[a, b], &[b, a]).is_empty());
+    }
+}
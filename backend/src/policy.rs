@@ -0,0 +1,86 @@
+use crate::auth::AuthenticatedUser;
+use crate::models::poll::{Poll, PollRole};
+use crate::models::user::Role;
+use crate::repositories::Repository;
+use actix_web::HttpResponse;
+use std::env;
+
+// Site-wide actions, decided by the user's role alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    CreatePoll,
+    Vote,
+    ManageRoles,
+    ReconcileTallies,
+    // Reading another user's ballot.
+    ViewAnyVote,
+}
+
+// Actions on one poll, decided by the user's role and their role on the poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollAction {
+    UpdateStatus,
+    ResetVotes,
+    ManageCollaborators,
+    // Seeing how many people are watching the poll live.
+    ViewViewers,
+}
+
+// Promotes the users listed in ADMIN_USER_IDS (comma separated) once at
+// startup, so a fresh deployment can appoint its first admin. Only accounts
+// that already exist are promoted; an id nobody has registered yet is skipped
+// rather than handed to whoever claims it first.
+pub async fn promote_bootstrap_admins(repo: &dyn Repository) {
+    let Ok(ids) = env::var("ADMIN_USER_IDS") else {
+        return;
+    };
+
+    for user_id in ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        match repo.update_user_role(user_id, Role::Admin).await {
+            Ok(true) => log::info!("Promoted {} to admin", user_id),
+            Ok(false) => log::warn!("ADMIN_USER_IDS lists {}, which has no account yet", user_id),
+            Err(e) => log::error!("Failed to promote {} to admin: {}", user_id, e),
+        }
+    }
+}
+
+pub fn can(user: &AuthenticatedUser, action: Action) -> bool {
+    match user.role {
+        Role::Admin => true,
+        Role::Member => matches!(action, Action::CreatePoll | Action::Vote),
+        Role::Viewer => false,
+    }
+}
+
+// The creator is always an owner; other users get their collaborator role.
+pub fn poll_role(user_id: &str, poll: &Poll) -> Option<PollRole> {
+    if poll.created_by == user_id {
+        return Some(PollRole::Owner);
+    }
+
+    poll.collaborators
+        .iter()
+        .find(|(collaborator, _)| collaborator == user_id)
+        .map(|(_, role)| *role)
+}
+
+pub fn can_on_poll(user: &AuthenticatedUser, poll: &Poll, action: PollAction) -> bool {
+    match user.role {
+        Role::Admin => return true,
+        Role::Viewer => return false,
+        Role::Member => {}
+    }
+
+    match poll_role(&user.user_id, poll) {
+        Some(PollRole::Owner) => true,
+        Some(PollRole::Editor) => {
+            matches!(action, PollAction::UpdateStatus | PollAction::ViewViewers)
+        }
+        Some(PollRole::Viewer) => action == PollAction::ViewViewers,
+        None => false,
+    }
+}
+
+pub fn forbidden(message: &str) -> HttpResponse {
+    HttpResponse::Forbidden().json(serde_json::json!({ "message": message }))
+}
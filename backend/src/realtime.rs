@@ -0,0 +1,4 @@
+pub mod coalescer;
+pub mod fanout;
+pub mod registry;
+pub mod shutdown;
\ No newline at end of file
@@ -0,0 +1,122 @@
+use crate::handlers::websocket::{PollUpdate, VoteResult};
+use crate::realtime::registry;
+use crate::repositories::Repository;
+use mongodb::bson::oid::ObjectId;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const DEFAULT_WINDOW_MS: u64 = 250;
+
+// Polls with a broadcast task running, and whether their votes changed since
+// that task last started reading results. One task per poll keeps broadcasts
+// in the order their results were read.
+static RUNNING: Lazy<Mutex<HashMap<ObjectId, bool>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// RESULTS_BROADCAST_WINDOW_MS controls how long votes are batched per poll.
+static WINDOW: Lazy<Duration> = Lazy::new(|| {
+    let window_ms = env::var("RESULTS_BROADCAST_WINDOW_MS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_WINDOW_MS);
+    Duration::from_millis(window_ms)
+});
+
+// Records that a poll's votes changed. The first change in a window schedules
+// one results broadcast at its end; later changes in the same window ride
+// along, so a hot poll is tallied and broadcast at most once per window.
+// Changes made while results are being read and published trigger one more
+// broadcast once that one is done.
+pub fn vote_changed(repo: Arc<dyn Repository>, poll_id: ObjectId) {
+    {
+        let mut running = RUNNING.lock().unwrap();
+        if let Some(dirty) = running.get_mut(&poll_id) {
+            *dirty = true;
+            return;
+        }
+        running.insert(poll_id, false);
+    }
+
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::time::sleep(*WINDOW).await;
+
+            // Everything up to here is covered by the read below.
+            RUNNING.lock().unwrap().insert(poll_id, false);
+            broadcast_results(repo.as_ref(), poll_id).await;
+
+            let mut running = RUNNING.lock().unwrap();
+            if running.get(&poll_id) != Some(&true) {
+                running.remove(&poll_id);
+                return;
+            }
+        }
+    });
+}
+
+async fn broadcast_results(repo: &dyn Repository, poll_id: ObjectId) {
+    match repo.get_poll_results(poll_id).await {
+        Ok(results) => {
+            registry::publish(PollUpdate::VoteUpdate {
+                poll_id: poll_id.to_hex(),
+                results: VoteResult::from_results(results),
+            })
+            .await
+        }
+        Err(e) => log::error!("Failed to load results for poll {}: {}", poll_id, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_memory_repository::InMemoryRepository;
+    use crate::models::vote::Vote;
+    use crate::realtime::registry::Subscription;
+
+    // Records a vote and reports the change, as casting one does.
+    async fn vote(repo: &Arc<dyn Repository>, poll_id: ObjectId, option_id: ObjectId, user: &str) {
+        repo.submit_or_update_vote(Vote::_new(poll_id, vec![option_id], user.to_string()))
+            .await
+            .unwrap();
+        vote_changed(repo.clone(), poll_id);
+    }
+
+    // The vote counts broadcast so far, one entry per results update.
+    fn broadcast_counts(subscription: &mut Subscription) -> Vec<i32> {
+        let mut counts = Vec::new();
+        while let Ok(event) = subscription.receiver.try_recv() {
+            if let PollUpdate::VoteUpdate { results, .. } = event.update {
+                counts.push(results.iter().map(|result| result.count).sum());
+            }
+        }
+        counts
+    }
+
+    #[actix_web::test]
+    async fn batches_votes_within_a_window() {
+        let repo: Arc<dyn Repository> = Arc::new(InMemoryRepository::new());
+        let (poll_id, option_id) = (ObjectId::new(), ObjectId::new());
+        let mut subscription = registry::subscribe(&poll_id.to_hex(), None).await;
+
+        for user in ["a", "b", "c"] {
+            vote(&repo, poll_id, option_id, user).await;
+        }
+        assert!(broadcast_counts(&mut subscription).is_empty());
+
+        tokio::time::sleep(*WINDOW * 2).await;
+        assert_eq!(broadcast_counts(&mut subscription), [3]);
+
+        // A change after the broadcast opens a new window.
+        vote(&repo, poll_id, option_id, "d").await;
+        tokio::time::sleep(*WINDOW / 2).await;
+        vote(&repo, poll_id, option_id, "e").await;
+        assert!(broadcast_counts(&mut subscription).is_empty());
+
+        tokio::time::sleep(*WINDOW * 2).await;
+        assert_eq!(broadcast_counts(&mut subscription), [5]);
+        assert!(!RUNNING.lock().unwrap().contains_key(&poll_id));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::handlers::websocket::PollUpdate;
+use crate::models::vote::Vote;
+use crate::realtime::{coalescer, registry};
+use crate::repositories::Repository;
+use async_trait::async_trait;
+use futures::{future, StreamExt};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::change_stream::event::ChangeStreamEvent;
+use mongodb::options::FullDocumentType;
+use mongodb::{Collection, Database};
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+// How long to wait before reopening a change stream that failed.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// Delivers changes made by this instance to every viewer of the poll,
+// wherever they are connected.
+#[async_trait]
+pub trait PollFanout: Send + Sync {
+    // Called after this instance changed a poll's status or reset its votes.
+    async fn publish(&self, update: PollUpdate);
+
+    // Called after this instance recorded a vote.
+    fn vote_changed(&self, repo: Arc<dyn Repository>, poll_id: ObjectId);
+}
+
+static FANOUT: OnceCell<Box<dyn PollFanout>> = OnceCell::new();
+
+// Replaces the default in-process fan-out. Must be called before serving.
+pub fn install(fanout: impl PollFanout + 'static) {
+    if FANOUT.set(Box::new(fanout)).is_err() {
+        panic!("Poll fan-out is already installed");
+    }
+}
+
+pub fn fanout() -> &'static dyn PollFanout {
+    FANOUT.get_or_init(|| Box::new(InProcessFanout)).as_ref()
+}
+
+// Broadcasts to the sessions connected to this process only, which is all of
+// them on a single-node deployment.
+pub struct InProcessFanout;
+
+#[async_trait]
+impl PollFanout for InProcessFanout {
+    async fn publish(&self, update: PollUpdate) {
+        registry::publish(update).await;
+    }
+
+    fn vote_changed(&self, repo: Arc<dyn Repository>, poll_id: ObjectId) {
+        coalescer::vote_changed(repo, poll_id);
+    }
+}
+
+// Derives updates from MongoDB change streams, so every instance sees every
+// change no matter which instance made it. Changes made here arrive back
+// through the streams too, so nothing is published directly.
+//
+// Needs MongoDB running as a replica set. Presence counts stay per instance.
+pub struct ChangeStreamFanout;
+
+impl ChangeStreamFanout {
+    // Starts watching the `votes` and `polls` collections.
+    pub fn start(db: &Database, repo: Arc<dyn Repository>) -> Self {
+        let votes = db.collection::<Vote>("votes");
+        let vote_pipeline = vec![doc! {
+            "$match": { "operationType": { "$in": ["insert", "update", "replace"] } }
+        }];
+        actix_web::rt::spawn(watch(votes, vote_pipeline, move |event| {
+            if let Some(vote) = event.full_document {
+                coalescer::vote_changed(repo.clone(), vote.poll_id);
+            }
+            future::ready(())
+        }));
+
+        let polls = db.collection::<Document>("polls");
+        // Ballot count bumps come with every new vote and aren't announced.
+        let poll_pipeline = vec![doc! {
+            "$match": {
+                "operationType": "update",
+                "$or": [
+                    { "updateDescription.updatedFields.isactive": { "$exists": true } },
+                    { "updateDescription.updatedFields.votes_reset_at": { "$exists": true } },
+                ],
+            }
+        }];
+        actix_web::rt::spawn(watch(polls, poll_pipeline, |event| {
+            let updates = poll_updates(event);
+            // Published before the next event is read, so clients see poll
+            // changes in the order they happened.
+            async move {
+                for update in updates {
+                    registry::publish(update).await;
+                }
+            }
+        }));
+
+        ChangeStreamFanout
+    }
+}
+
+#[async_trait]
+impl PollFanout for ChangeStreamFanout {
+    async fn publish(&self, _update: PollUpdate) {}
+
+    fn vote_changed(&self, _repo: Arc<dyn Repository>, _poll_id: ObjectId) {}
+}
+
+fn poll_updates(event: ChangeStreamEvent<Document>) -> Vec<PollUpdate> {
+    let (Some(key), Some(description)) = (event.document_key, event.update_description) else {
+        return Vec::new();
+    };
+    let Ok(poll_id) = key.get_object_id("_id") else {
+        return Vec::new();
+    };
+    let poll_id = poll_id.to_hex();
+    let fields = description.updated_fields;
+
+    let mut updates = Vec::new();
+    if let Ok(is_active) = fields.get_bool("isactive") {
+        updates.push(PollUpdate::StatusUpdate {
+            poll_id: poll_id.clone(),
+            is_active,
+        });
+    }
+    // Deleted votes don't say which poll they belonged to, so a reset is
+    // announced by stamping the poll.
+    if fields.contains_key("votes_reset_at") {
+        updates.push(PollUpdate::Reset { poll_id });
+    }
+    updates
+}
+
+// Runs `handle` for every event on the collection's change stream, one at a
+// time, reopening the stream where it left off after errors.
+async fn watch<T, F, Fut>(collection: Collection<T>, pipeline: Vec<Document>, mut handle: F)
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+    F: FnMut(ChangeStreamEvent<T>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut resume_token = None;
+    loop {
+        let stream = collection
+            .watch()
+            .pipeline(pipeline.clone())
+            .full_document(FullDocumentType::UpdateLookup)
+            .resume_after(resume_token.clone())
+            .await;
+
+        match stream {
+            Ok(mut stream) => {
+                while let Some(event) = stream.next().await {
+                    match event {
+                        Ok(event) => handle(event).await,
+                        Err(e) => {
+                            log::error!("Change stream on {} failed: {}", collection.name(), e);
+                            break;
+                        }
+                    }
+                }
+                resume_token = stream.resume_token();
+            }
+            Err(e) => {
+                log::error!("Failed to watch {}: {}", collection.name(), e);
+                // The resume point may have aged out of the oplog.
+                resume_token = None;
+            }
+        }
+
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
@@ -0,0 +1,371 @@
+use crate::handlers::websocket::{PollEvent, PollUpdate};
+use crate::utils::instance::_instance_id;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::sync::RwLock;
+
+const CHANNEL_CAPACITY: usize = 100;
+
+// Recent events kept per poll so a reconnecting client can resume.
+const HISTORY_LEN: usize = 100;
+
+// How long the history of a poll nobody is watching is kept.
+const RESUME_WINDOW: Duration = Duration::from_secs(300);
+
+// Idle polls are looked for on publish and subscribe, at most this often.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(30);
+
+// Viewer count changes within this window are announced once, so a burst of
+// connects and disconnects produces a single presence update.
+const PRESENCE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+// One broadcast channel per poll with live subscribers. A channel is created
+// by its first subscriber and dropped with its last, so publishing an update
+// only wakes the receivers watching that poll.
+struct PollChannel {
+    sender: broadcast::Sender<PollEvent>,
+    subscribers: HashSet<usize>,
+}
+
+// Presence counts are left out: they are superseded within seconds and would
+// otherwise push the updates a resuming client needs out of the buffer.
+struct PollHistory {
+    events: VecDeque<PollEvent>,
+    // Highest sequence number dropped from `events`; resuming from before it
+    // would skip updates.
+    evicted_through: u64,
+    updated_at: Instant,
+}
+
+#[derive(Default)]
+struct Registry {
+    channels: HashMap<String, PollChannel>,
+    // Last sequence number published per poll, dropped along with its history.
+    sequences: HashMap<String, u64>,
+    // Highest sequence number dropped so far. A poll's numbering starts above
+    // it, so it never goes backwards while the process runs.
+    sequence_floor: u64,
+    histories: HashMap<String, PollHistory>,
+    pruned_at: Option<Instant>,
+    // Polls with a presence update waiting out the debounce window.
+    presence_pending: HashSet<String>,
+    // Viewer count last announced per poll, for polls with viewers.
+    presence_announced: HashMap<String, usize>,
+}
+
+impl Registry {
+    // Forgets polls that nobody has watched or updated for `RESUME_WINDOW`.
+    fn prune(&mut self, now: Instant) {
+        let channels = &self.channels;
+        self.histories.retain(|poll_id, history| {
+            channels.contains_key(poll_id)
+                || now.saturating_duration_since(history.updated_at) < RESUME_WINDOW
+        });
+
+        let (histories, floor) = (&self.histories, &mut self.sequence_floor);
+        self.sequences.retain(|poll_id, seq| {
+            let keep = histories.contains_key(poll_id);
+            if !keep {
+                *floor = (*floor).max(*seq);
+            }
+            keep
+        });
+
+        self.pruned_at = Some(now);
+    }
+
+    fn prune_if_due(&mut self) {
+        if self
+            .pruned_at
+            .is_none_or(|pruned_at| pruned_at.elapsed() >= PRUNE_INTERVAL)
+        {
+            self.prune(Instant::now());
+        }
+    }
+
+    fn viewers(&self, poll_id: &str) -> usize {
+        self.channels
+            .get(poll_id)
+            .map_or(0, |channel| channel.subscribers.len())
+    }
+
+    // Stamps the update with the poll's next sequence number, records it for
+    // resuming clients and sends it to the poll's subscribers.
+    fn publish(&mut self, update: PollUpdate) {
+        self.prune_if_due();
+        let poll_id = update.poll_id().to_string();
+
+        let seq = self
+            .sequences
+            .entry(poll_id.clone())
+            .or_insert(self.sequence_floor);
+        *seq += 1;
+        let event = PollEvent {
+            epoch: epoch(),
+            seq: *seq,
+            update,
+        };
+
+        let history = self
+            .histories
+            .entry(poll_id.clone())
+            .or_insert_with(|| PollHistory {
+                events: VecDeque::with_capacity(HISTORY_LEN),
+                evicted_through: event.seq - 1,
+                updated_at: Instant::now(),
+            });
+        if !matches!(event.update, PollUpdate::Presence { .. }) {
+            if history.events.len() == HISTORY_LEN {
+                if let Some(evicted) = history.events.pop_front() {
+                    history.evicted_through = evicted.seq;
+                }
+            }
+            history.events.push_back(event.clone());
+        }
+        history.updated_at = Instant::now();
+
+        if let Some(channel) = self.channels.get(&poll_id) {
+            let _ = channel.sender.send(event);
+        }
+    }
+
+    // The events published after `resume_from`, or `None` when some are no
+    // longer buffered and the client needs a snapshot instead.
+    fn replay(&self, poll_id: &str, resume_from: &ResumePoint) -> Option<Vec<PollEvent>> {
+        let seq = self.sequences.get(poll_id).copied().unwrap_or(0);
+        if resume_from.epoch != epoch() || resume_from.seq > seq {
+            return None;
+        }
+        if resume_from.seq == seq {
+            return Some(Vec::new());
+        }
+
+        let history = self.histories.get(poll_id)?;
+        (history.evicted_through <= resume_from.seq).then(|| {
+            history
+                .events
+                .iter()
+                .filter(|event| event.seq > resume_from.seq)
+                .cloned()
+                .collect()
+        })
+    }
+
+    // Announces the poll's viewer count once the debounce window has passed,
+    // unless an announcement is already on its way.
+    fn schedule_presence(&mut self, poll_id: &str) {
+        if !self.presence_pending.insert(poll_id.to_string()) {
+            return;
+        }
+
+        let poll_id = poll_id.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(PRESENCE_DEBOUNCE).await;
+
+            let mut registry = REGISTRY.write().await;
+            registry.presence_pending.remove(&poll_id);
+
+            let viewers = registry.viewers(&poll_id);
+            let announced = registry
+                .presence_announced
+                .get(&poll_id)
+                .copied()
+                .unwrap_or(0);
+            if viewers == announced {
+                return;
+            }
+
+            if viewers == 0 {
+                registry.presence_announced.remove(&poll_id);
+            } else {
+                registry.presence_announced.insert(poll_id.clone(), viewers);
+            }
+            registry.publish(PollUpdate::Presence { poll_id, viewers });
+        });
+    }
+}
+
+static REGISTRY: Lazy<RwLock<Registry>> = Lazy::new(|| RwLock::new(Registry::default()));
+
+static SUBSCRIBER_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Subscription {
+    pub id: usize,
+    pub receiver: broadcast::Receiver<PollEvent>,
+    // Sequence number of the last update published before subscribing; every
+    // event on `receiver` has a higher one.
+    pub seq: u64,
+    // Events after the requested resume point, or `None` when they are no
+    // longer available and the subscriber needs a fresh snapshot instead.
+    pub replay: Option<Vec<PollEvent>>,
+}
+
+// Sequence numbers count per poll within one run of one instance. Events
+// carry this epoch so a client resuming against a restarted or different
+// instance is detected and given a snapshot instead of unrelated events.
+pub fn epoch() -> &'static str {
+    _instance_id()
+}
+
+// Where a client wants to resume: the epoch and sequence number of the last
+// event it applied.
+pub struct ResumePoint {
+    pub epoch: String,
+    pub seq: u64,
+}
+
+// Subscribes to a poll's updates. With `resume_from`, the events published
+// after that point are returned for replay if still buffered.
+pub async fn subscribe(poll_id: &str, resume_from: Option<ResumePoint>) -> Subscription {
+    let id = SUBSCRIBER_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    let mut registry = REGISTRY.write().await;
+    registry.prune_if_due();
+
+    let seq = registry.sequences.get(poll_id).copied().unwrap_or(0);
+    let replay = resume_from.and_then(|resume_from| registry.replay(poll_id, &resume_from));
+
+    let channel = registry
+        .channels
+        .entry(poll_id.to_string())
+        .or_insert_with(|| PollChannel {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+            subscribers: HashSet::new(),
+        });
+    channel.subscribers.insert(id);
+    let receiver = channel.sender.subscribe();
+
+    registry.schedule_presence(poll_id);
+
+    Subscription {
+        id,
+        receiver,
+        seq,
+        replay,
+    }
+}
+
+pub async fn unsubscribe(poll_id: &str, subscriber_id: usize) {
+    let mut registry = REGISTRY.write().await;
+    if let Some(channel) = registry.channels.get_mut(poll_id) {
+        channel.subscribers.remove(&subscriber_id);
+        if channel.subscribers.is_empty() {
+            registry.channels.remove(poll_id);
+        }
+        registry.schedule_presence(poll_id);
+    }
+}
+
+// Number of websocket and event-stream sessions watching a poll.
+pub async fn viewers(poll_id: &str) -> usize {
+    REGISTRY.read().await.viewers(poll_id)
+}
+
+// Sequence number of the last update published for a poll.
+pub async fn current_seq(poll_id: &str) -> u64 {
+    let registry = REGISTRY.read().await;
+    registry.sequences.get(poll_id).copied().unwrap_or(0)
+}
+
+pub async fn publish(update: PollUpdate) {
+    REGISTRY.write().await.publish(update);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reset(poll_id: &str) -> PollUpdate {
+        PollUpdate::Reset {
+            poll_id: poll_id.to_string(),
+        }
+    }
+
+    fn resume(seq: u64) -> ResumePoint {
+        ResumePoint {
+            epoch: epoch().to_string(),
+            seq,
+        }
+    }
+
+    fn seqs(events: Option<Vec<PollEvent>>) -> Option<Vec<u64>> {
+        events.map(|events| events.iter().map(|event| event.seq).collect())
+    }
+
+    #[test]
+    fn replays_buffered_events_after_the_resume_point() {
+        let mut registry = Registry::default();
+        registry.publish(reset("p"));
+        registry.publish(reset("p"));
+        registry.publish(PollUpdate::Presence {
+            poll_id: "p".to_string(),
+            viewers: 2,
+        });
+        registry.publish(reset("other"));
+        registry.publish(reset("p"));
+
+        // Presence counts are numbered but not kept for replay.
+        assert_eq!(seqs(registry.replay("p", &resume(1))), Some(vec![2, 4]));
+        assert_eq!(seqs(registry.replay("p", &resume(4))), Some(vec![]));
+        assert_eq!(seqs(registry.replay("other", &resume(0))), Some(vec![1]));
+    }
+
+    #[test]
+    fn falls_back_to_a_snapshot_when_events_are_gone() {
+        let mut registry = Registry::default();
+        for _ in 0..HISTORY_LEN + 5 {
+            registry.publish(reset("p"));
+        }
+
+        let elsewhere = ResumePoint {
+            epoch: "another-instance".to_string(),
+            seq: 50,
+        };
+        assert_eq!(seqs(registry.replay("p", &elsewhere)), None);
+        assert_eq!(
+            seqs(registry.replay("p", &resume(HISTORY_LEN as u64 + 6))),
+            None
+        );
+
+        // Events 1 to 5 were pushed out of the buffer.
+        assert_eq!(seqs(registry.replay("p", &resume(4))), None);
+        let replay = seqs(registry.replay("p", &resume(5))).unwrap();
+        assert_eq!(replay.len(), HISTORY_LEN);
+        assert_eq!(replay.first(), Some(&6));
+    }
+
+    #[test]
+    fn pruning_idle_polls_keeps_numbering_increasing() {
+        let mut registry = Registry::default();
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        registry.channels.insert(
+            "watched".to_string(),
+            PollChannel {
+                sender,
+                subscribers: HashSet::from([0]),
+            },
+        );
+        for _ in 0..3 {
+            registry.publish(reset("idle"));
+            registry.publish(reset("watched"));
+        }
+
+        registry.prune(Instant::now() + RESUME_WINDOW);
+        assert_eq!(registry.sequences.keys().collect::<Vec<_>>(), ["watched"]);
+        assert!(!registry.histories.contains_key("idle"));
+        assert_eq!(seqs(registry.replay("idle", &resume(3))), None);
+        assert_eq!(
+            seqs(registry.replay("watched", &resume(1))),
+            Some(vec![2, 3])
+        );
+
+        // Numbering picks up above anything handed out before, so a client
+        // that saw event 3 resumes with exactly the events since.
+        registry.publish(reset("idle"));
+        assert_eq!(seqs(registry.replay("idle", &resume(3))), Some(vec![4]));
+        assert_eq!(seqs(registry.replay("idle", &resume(2))), None);
+    }
+}
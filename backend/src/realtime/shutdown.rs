@@ -0,0 +1,14 @@
+use once_cell::sync::Lazy;
+use tokio::sync::watch;
+
+// Set once the server starts shutting down, so long-lived streams can close
+// cleanly instead of being dropped when the workers stop.
+static SHUTDOWN: Lazy<watch::Sender<bool>> = Lazy::new(|| watch::channel(false).0);
+
+pub fn subscribe() -> watch::Receiver<bool> {
+    SHUTDOWN.subscribe()
+}
+
+pub fn begin() {
+    SHUTDOWN.send_replace(true);
+}
@@ -0,0 +1,204 @@
+use crate::repositories::poll_query::PollQuery;
+use crate::repositories::Repository;
+use crate::utils::instance::_instance_id;
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+use std::env;
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_INTERVAL_SECS: u64 = 3600;
+const LEASE_NAME: &str = "tally_reconciliation";
+const PAGE_SIZE: usize = 100;
+
+// Set while this instance is running a reconciliation, scheduled or requested.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+// An option whose maintained counter disagrees with its ballots.
+#[derive(Serialize)]
+pub struct TallyDrift {
+    pub poll_id: String,
+    pub option_id: String,
+    pub stored: i32,
+    pub recounted: i32,
+}
+
+// Starts the background task that recounts every poll's ballots and reports
+// counters that drifted. TALLY_RECONCILE_INTERVAL_SECS sets how often it runs;
+// with TALLY_RECONCILE_REPAIR=true it also overwrites drifted counters. Only
+// the replica holding the reconciliation lease runs it; the lease outlives one
+// interval so another replica takes over if the holder goes away.
+pub fn spawn(repo: Arc<dyn Repository>) {
+    let repair = env::var("TALLY_RECONCILE_REPAIR").as_deref() == Ok("true");
+
+    actix_web::rt::spawn(async move {
+        let mut interval = tokio::time::interval(period());
+        loop {
+            interval.tick().await;
+            if start(repo.as_ref()).await {
+                run(repo.as_ref(), repair).await;
+            }
+        }
+    });
+}
+
+// Starts a reconciliation that repairs drifted counters in the background.
+// Returns false if one is already running, here or on another replica.
+pub async fn start_repair(repo: Arc<dyn Repository>) -> bool {
+    if !start(repo.as_ref()).await {
+        return false;
+    }
+    actix_web::rt::spawn(async move { run(repo.as_ref(), true).await });
+    true
+}
+
+fn period() -> Duration {
+    let interval_secs = env::var("TALLY_RECONCILE_INTERVAL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_INTERVAL_SECS);
+    Duration::from_secs(interval_secs)
+}
+
+fn lease_ttl() -> Duration {
+    let period = period();
+    period + period / 2
+}
+
+// Claims the right to run: no run in progress here, and the lease.
+async fn start(repo: &dyn Repository) -> bool {
+    if RUNNING.swap(true, Ordering::AcqRel) {
+        return false;
+    }
+
+    match repo
+        .acquire_lease(LEASE_NAME, _instance_id(), lease_ttl())
+        .await
+    {
+        Ok(true) => return true,
+        Ok(false) => {}
+        Err(e) => log::error!("Failed to acquire the tally reconciliation lease: {}", e),
+    }
+    RUNNING.store(false, Ordering::Release);
+    false
+}
+
+// Reconciles every poll under the lease taken by `start`, then lets another
+// run start.
+async fn run(repo: &dyn Repository, repair: bool) {
+    match reconcile(repo, repair, Some(lease_ttl())).await {
+        Ok(drift) => {
+            for entry in &drift {
+                log::warn!(
+                    "Tally drift on poll {} option {}: stored {}, recounted {}{}",
+                    entry.poll_id,
+                    entry.option_id,
+                    entry.stored,
+                    entry.recounted,
+                    if repair { " (repaired)" } else { "" }
+                );
+            }
+        }
+        Err(e) => log::error!("Failed to reconcile tallies: {}", e),
+    }
+    RUNNING.store(false, Ordering::Release);
+}
+
+// Compares every poll's counters with a recount of its ballots. With a
+// `lease` TTL, the lease is renewed before each page and the scan stops if
+// another replica has taken it over.
+pub async fn reconcile(
+    repo: &dyn Repository,
+    repair: bool,
+    lease: Option<Duration>,
+) -> Result<Vec<TallyDrift>, Box<dyn Error>> {
+    let mut drift = Vec::new();
+    let mut query = PollQuery {
+        limit: PAGE_SIZE,
+        ..PollQuery::default()
+    };
+
+    loop {
+        if let Some(ttl) = lease {
+            if !repo.acquire_lease(LEASE_NAME, _instance_id(), ttl).await? {
+                return Err("Lost the tally reconciliation lease".into());
+            }
+        }
+
+        let page = repo.list_polls(&query).await?;
+        for poll in &page.polls {
+            let Some(poll_id) = poll.id else { continue };
+            drift.extend(check_poll(repo, poll_id, repair).await?);
+        }
+
+        match page.next_cursor {
+            Some(cursor) => query.after = Some(cursor),
+            None => return Ok(drift),
+        }
+    }
+}
+
+async fn check_poll(
+    repo: &dyn Repository,
+    poll_id: ObjectId,
+    repair: bool,
+) -> Result<Vec<TallyDrift>, Box<dyn Error>> {
+    let check = repo.reconcile_poll_results(poll_id, repair).await?;
+    let mut drift = Vec::new();
+
+    let mut option_ids: Vec<_> = check.stored.iter().map(|(id, _)| *id).collect();
+    for (option_id, _) in &check.recounted {
+        if !option_ids.contains(option_id) {
+            option_ids.push(*option_id);
+        }
+    }
+
+    let count_in = |counts: &[(_, i32)], option_id| {
+        counts
+            .iter()
+            .find(|(id, _)| *id == option_id)
+            .map_or(0, |(_, count)| *count)
+    };
+
+    for option_id in option_ids {
+        let stored = count_in(&check.stored, option_id);
+        let recounted = count_in(&check.recounted, option_id);
+        if stored != recounted {
+            drift.push(TallyDrift {
+                poll_id: poll_id.to_hex(),
+                option_id: option_id.to_hex(),
+                stored,
+                recounted,
+            });
+        }
+    }
+
+    Ok(drift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_memory_repository::InMemoryRepository;
+
+    #[actix_web::test]
+    async fn leaves_the_scan_to_the_lease_holder() {
+        let repo: Arc<dyn Repository> = Arc::new(InMemoryRepository::new());
+        let ttl = Duration::from_secs(60);
+        assert!(repo
+            .acquire_lease(LEASE_NAME, "another-replica", ttl)
+            .await
+            .unwrap());
+
+        assert!(reconcile(repo.as_ref(), true, Some(ttl)).await.is_err());
+        assert!(!start_repair(repo.clone()).await);
+
+        // The read-only report doesn't need the lease.
+        assert!(reconcile(repo.as_ref(), false, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,17 +1,37 @@
-use crate::models::{poll::Poll, vote::Vote, user::User};
+pub mod poll_query;
+
+use crate::models::{credential::Credential, poll::{Poll, PollRole}, vote::Vote, user::{Role, User}};
+use crate::models::token::{PendingLogin, RefreshToken, RevokedToken};
 use async_trait::async_trait;
+use poll_query::{PollPage, PollQuery};
 use mongodb::bson::oid::ObjectId;
 use std::error::Error;
+use std::fmt;
+use std::time::Duration;
 
 #[async_trait]
 pub trait PollRepository {
     async fn create_poll(&self, poll: Poll) -> Result<(), Box<dyn Error>>;
     async fn get_poll_by_id(&self, id: ObjectId) -> Result<Option<Poll>, Box<dyn Error>>;
-    async fn get_all_polls(&self) -> Result<Vec<Poll>, Box<dyn Error>>;
-    async fn get_polls_by_user(&self, user_id: &str) -> Result<Vec<Poll>, Box<dyn Error>>;
+    // One page of the polls matching the query, in its sort order.
+    async fn list_polls(&self, query: &PollQuery) -> Result<PollPage, Box<dyn Error>>;
+    // Any status change supersedes the pending schedule: opening clears
+    // `opens_at`, closing clears both `opens_at` and `closes_at`.
     async fn update_poll_status(&self, id: ObjectId, is_active: bool) -> Result<(), Box<dyn Error>>;
+    async fn get_scheduled_polls(&self) -> Result<Vec<Poll>, Box<dyn Error>>;
+    // Like `update_poll_status`, but only while the poll still has the status
+    // and schedule it was read with. Returns false if it has changed since,
+    // e.g. because its owner opened or closed it by hand.
+    async fn apply_poll_schedule(&self, poll: &Poll, is_active: bool) -> Result<bool, Box<dyn Error>>;
+    async fn update_poll_collaborators(&self, id: ObjectId, collaborators: Vec<(String, PollRole)>) -> Result<(), Box<dyn Error>>;
     async fn reset_votes_for_poll(&self, poll_id: ObjectId) -> Result<(), Box<dyn Error>>;
+    // Reads the per-option counters kept up to date as votes change. Options
+    // nobody picked are omitted.
     async fn get_poll_results(&self, poll_id: ObjectId) -> Result<Vec<(ObjectId, i32)>, Box<dyn Error>>;
+    // Recounts a poll's stored ballots and compares them with its counters, in
+    // one consistent read. With `repair`, the counters are replaced by the
+    // recount in the same step.
+    async fn reconcile_poll_results(&self, poll_id: ObjectId, repair: bool) -> Result<TallyCheck, Box<dyn Error>>;
     async fn find_polls_by_ids(&self, poll_ids: Vec<ObjectId>) -> Result<Vec<Poll>, Box<dyn Error>>;
 }
 
@@ -19,14 +39,81 @@ pub trait PollRepository {
 pub trait VoteRepository {
     async fn find_votes_by_user(&self, user_id: &str) -> Result<Vec<Vote>, Box<dyn Error>>;
     async fn find_vote(&self, poll_id: ObjectId, user_id: &str) -> Result<Option<Vote>, Box<dyn Error>>;
+    async fn find_votes_by_poll(&self, poll_id: ObjectId) -> Result<Vec<Vote>, Box<dyn Error>>;
+    // The subset of `poll_ids` this user has voted on.
+    async fn find_voted_poll_ids(&self, user_id: &str, poll_ids: Vec<ObjectId>) -> Result<Vec<ObjectId>, Box<dyn Error>>;
+    // Stores the vote and moves the poll's counters from the user's previous
+    // choices to the new ones atomically. Fails with `VoteConflict` if a
+    // concurrent write for the same poll and user got in first.
     async fn submit_or_update_vote(&self, vote: Vote) -> Result<(), Box<dyn Error>>;
 }
 
+// Counters as stored and as recounted from ballots, by option.
+pub struct TallyCheck {
+    pub stored: Vec<(ObjectId, i32)>,
+    pub recounted: Vec<(ObjectId, i32)>,
+}
+
+#[derive(Debug)]
+pub struct VoteConflict;
+
+impl fmt::Display for VoteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "A vote for this poll and user was written concurrently")
+    }
+}
+
+impl Error for VoteConflict {}
+
+#[derive(Debug)]
+pub struct AccountExists;
+
+impl fmt::Display for AccountExists {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "An account with this user ID already exists")
+    }
+}
+
+impl Error for AccountExists {}
+
 #[async_trait]
 pub trait UserRepository {
     async fn store_user(&self, user: User) -> Result<(), Box<dyn Error>>;
     async fn find_user_by_id(&self, user_id: &str) -> Result<Option<User>, Box<dyn Error>>;
+    // Users that don't exist are omitted.
+    async fn find_users_by_ids(&self, user_ids: Vec<String>) -> Result<Vec<User>, Box<dyn Error>>;
+    // Returns false if no such user exists.
+    async fn update_user_role(&self, user_id: &str, role: Role) -> Result<bool, Box<dyn Error>>;
+    // Creates the user together with its credential. Fails with
+    // `AccountExists` if the user ID is taken, with or without a password:
+    // users created before passwords existed can't be claimed by registering.
+    async fn create_account(&self, user: User, credential: Credential) -> Result<(), Box<dyn Error>>;
+    async fn find_credential(&self, user_id: &str) -> Result<Option<Credential>, Box<dyn Error>>;
+}
+
+#[async_trait]
+pub trait TokenRepository {
+    async fn store_refresh_token(&self, token: RefreshToken) -> Result<(), Box<dyn Error>>;
+    async fn find_refresh_token(&self, token_hash: &str) -> Result<Option<RefreshToken>, Box<dyn Error>>;
+    // Marks an unused token as used. Returns false if it had already been used,
+    // so concurrent rotations of the same token cannot both succeed.
+    async fn mark_refresh_token_used(&self, token_hash: &str) -> Result<bool, Box<dyn Error>>;
+    async fn revoke_token_family(&self, family_id: &str) -> Result<(), Box<dyn Error>>;
+    async fn revoke_access_token(&self, token: RevokedToken) -> Result<(), Box<dyn Error>>;
+    async fn is_access_token_revoked(&self, jti: &str) -> Result<bool, Box<dyn Error>>;
+    async fn store_pending_login(&self, login: PendingLogin) -> Result<(), Box<dyn Error>>;
+    // Removes and returns the unexpired login started with `state`, so each
+    // state completes at most one login.
+    async fn take_pending_login(&self, state: &str) -> Result<Option<PendingLogin>, Box<dyn Error>>;
+}
+
+#[async_trait]
+pub trait LeaseRepository {
+    // Takes the named lease for `holder`, or renews it if `holder` already has
+    // it, until `ttl` from now. Returns false while another holder's lease is
+    // unexpired, so background jobs can run on a single replica.
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: Duration) -> Result<bool, Box<dyn Error>>;
 }
 
 #[async_trait]
-pub trait Repository: PollRepository + VoteRepository + UserRepository + Send + Sync {}
+pub trait Repository: PollRepository + VoteRepository + UserRepository + TokenRepository + LeaseRepository + Send + Sync {}
@@ -0,0 +1,251 @@
+use crate::models::poll::Poll;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PollSort {
+    #[default]
+    Newest,
+    MostVotes,
+    // Polls with a closing time first, soonest first; then the rest, newest
+    // first.
+    ClosingSoonest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PollStatusFilter {
+    Active,
+    Closed,
+}
+
+// Position after the last poll of a page, holding that poll's sort key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PollCursor {
+    Newest {
+        id: ObjectId,
+    },
+    MostVotes {
+        votes: i64,
+        id: ObjectId,
+    },
+    // `closes_at` as stored, or `None` for polls without a closing time.
+    ClosingSoonest {
+        closes_at: Option<String>,
+        id: ObjectId,
+    },
+}
+
+impl PollCursor {
+    // The cursor positioned at `poll`, which must have an id.
+    pub fn at(poll: &Poll, sort: PollSort, votes: i64) -> Self {
+        let id = poll.id.unwrap_or_default();
+        match sort {
+            PollSort::Newest => PollCursor::Newest { id },
+            PollSort::MostVotes => PollCursor::MostVotes { votes, id },
+            PollSort::ClosingSoonest => PollCursor::ClosingSoonest {
+                closes_at: poll.closes_at.as_ref().map(stored_timestamp),
+                id,
+            },
+        }
+    }
+
+    // Orders cursors of the same sort the way their polls are listed.
+    pub fn cmp_in_order(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (PollCursor::Newest { id: a }, PollCursor::Newest { id: b }) => b.cmp(a),
+            (
+                PollCursor::MostVotes {
+                    votes: a_votes,
+                    id: a,
+                },
+                PollCursor::MostVotes {
+                    votes: b_votes,
+                    id: b,
+                },
+            ) => b_votes.cmp(a_votes).then(b.cmp(a)),
+            (
+                PollCursor::ClosingSoonest {
+                    closes_at: a_closes,
+                    id: a,
+                },
+                PollCursor::ClosingSoonest {
+                    closes_at: b_closes,
+                    id: b,
+                },
+            ) => {
+                let closing = match (a_closes, b_closes) {
+                    (Some(a_closes), Some(b_closes)) => a_closes.cmp(b_closes),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                };
+                closing.then(b.cmp(a))
+            }
+            _ => Ordering::Equal,
+        }
+    }
+
+    pub fn sort(&self) -> PollSort {
+        match self {
+            PollCursor::Newest { .. } => PollSort::Newest,
+            PollCursor::MostVotes { .. } => PollSort::MostVotes,
+            PollCursor::ClosingSoonest { .. } => PollSort::ClosingSoonest,
+        }
+    }
+
+    // Opaque to clients, who pass it back as `after`.
+    pub fn encode(&self) -> String {
+        let raw = match self {
+            PollCursor::Newest { id } => format!("n:{}", id.to_hex()),
+            PollCursor::MostVotes { votes, id } => format!("v:{}:{}", id.to_hex(), votes),
+            PollCursor::ClosingSoonest { closes_at, id } => match closes_at {
+                Some(closes_at) => format!("c:{}:{}", id.to_hex(), closes_at),
+                None => format!("o:{}", id.to_hex()),
+            },
+        };
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        let mut parts = raw.splitn(3, ':');
+        let kind = parts.next()?;
+        let id = ObjectId::parse_str(parts.next()?).ok()?;
+        let key = parts.next();
+
+        match (kind, key) {
+            ("n", None) => Some(PollCursor::Newest { id }),
+            ("v", Some(votes)) => Some(PollCursor::MostVotes {
+                votes: votes.parse().ok()?,
+                id,
+            }),
+            ("c", Some(closes_at)) => Some(PollCursor::ClosingSoonest {
+                closes_at: Some(closes_at.to_string()),
+                id,
+            }),
+            ("o", None) => Some(PollCursor::ClosingSoonest {
+                closes_at: None,
+                id,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Timestamps are stored in their serde form, so sort keys read from the
+// database and built in process compare the same way.
+pub fn stored_timestamp(timestamp: &DateTime<Utc>) -> String {
+    match serde_json::to_value(timestamp) {
+        Ok(serde_json::Value::String(timestamp)) => timestamp,
+        _ => timestamp.to_rfc3339(),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PollQuery {
+    pub limit: usize,
+    // Must have been produced for the same `sort`.
+    pub after: Option<PollCursor>,
+    pub status: Option<PollStatusFilter>,
+    pub created_by: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    // Keeps only the polls this user has voted on (`true`) or not (`false`).
+    pub voted_by: Option<(String, bool)>,
+    pub sort: PollSort,
+}
+
+pub struct PollPage {
+    pub polls: Vec<Poll>,
+    // Ballots cast per listed poll, as kept alongside the poll.
+    pub ballot_counts: HashMap<ObjectId, i64>,
+    // Present when more polls follow.
+    pub next_cursor: Option<PollCursor>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let id = ObjectId::new();
+        let cursors = [
+            PollCursor::Newest { id },
+            PollCursor::MostVotes { votes: 42, id },
+            PollCursor::ClosingSoonest {
+                closes_at: Some("2026-10-18T09:00:00Z".to_string()),
+                id,
+            },
+            PollCursor::ClosingSoonest {
+                closes_at: None,
+                id,
+            },
+        ];
+
+        for cursor in cursors {
+            assert_eq!(PollCursor::decode(&cursor.encode()), Some(cursor));
+        }
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let id = ObjectId::new().to_hex();
+        for raw in [
+            "",
+            "n",
+            "x:{id}",
+            "n:not-an-id",
+            "v:{id}",
+            "v:{id}:many",
+            "o:{id}:extra",
+        ] {
+            let cursor = URL_SAFE_NO_PAD.encode(raw.replace("{id}", &id));
+            assert_eq!(PollCursor::decode(&cursor), None, "{}", raw);
+        }
+        assert_eq!(PollCursor::decode("not base64!"), None);
+    }
+
+    #[test]
+    fn cursors_order_like_their_listings() {
+        let (older, newer) = (ObjectId::new(), ObjectId::new());
+
+        let newest = |id| PollCursor::Newest { id };
+        assert_eq!(newest(newer).cmp_in_order(&newest(older)), Ordering::Less);
+
+        let most_votes = |votes, id| PollCursor::MostVotes { votes, id };
+        assert_eq!(
+            most_votes(5, older).cmp_in_order(&most_votes(3, newer)),
+            Ordering::Less
+        );
+        assert_eq!(
+            most_votes(3, newer).cmp_in_order(&most_votes(3, older)),
+            Ordering::Less
+        );
+
+        let closing = |closes_at: Option<&str>, id| PollCursor::ClosingSoonest {
+            closes_at: closes_at.map(str::to_string),
+            id,
+        };
+        let (soon, later) = (Some("2026-10-18T09:00:00Z"), Some("2026-10-19T09:00:00Z"));
+        assert_eq!(
+            closing(soon, older).cmp_in_order(&closing(later, newer)),
+            Ordering::Less
+        );
+        assert_eq!(
+            closing(later, older).cmp_in_order(&closing(None, newer)),
+            Ordering::Less
+        );
+        assert_eq!(
+            closing(None, newer).cmp_in_order(&closing(None, older)),
+            Ordering::Less
+        );
+    }
+}
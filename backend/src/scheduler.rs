@@ -0,0 +1,181 @@
+use crate::handlers::websocket::{broadcast_poll_update, PollUpdate};
+use crate::repositories::Repository;
+use crate::utils::instance::_instance_id;
+use chrono::Utc;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_INTERVAL_SECS: u64 = 1;
+const LEASE_NAME: &str = "poll_scheduler";
+// Intervals a replica can miss before another one takes over.
+const LEASE_INTERVALS: u32 = 5;
+
+// Starts the background task that opens and closes polls on schedule.
+// POLL_SCHEDULER_INTERVAL_SECS controls how often schedules are checked. Only
+// the replica holding the scheduler lease applies schedules, so each status
+// change is made and broadcast once.
+pub fn spawn(repo: Arc<dyn Repository>) {
+    let interval_secs = env::var("POLL_SCHEDULER_INTERVAL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_INTERVAL_SECS);
+
+    let period = Duration::from_secs(interval_secs);
+    let lease = period * LEASE_INTERVALS;
+
+    actix_web::rt::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            match repo.acquire_lease(LEASE_NAME, _instance_id(), lease).await {
+                Ok(true) => apply_schedules(repo.as_ref()).await,
+                Ok(false) => {}
+                Err(e) => log::error!("Failed to acquire the scheduler lease: {}", e),
+            }
+        }
+    });
+}
+
+async fn apply_schedules(repo: &dyn Repository) {
+    let polls = match repo.get_scheduled_polls().await {
+        Ok(polls) => polls,
+        Err(e) => {
+            log::error!("Failed to load scheduled polls: {}", e);
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    for poll in polls {
+        let Some(poll_id) = poll.id else { continue };
+
+        let is_active = if poll.closes_at.is_some_and(|closes_at| closes_at <= now) {
+            false
+        } else if poll.opens_at.is_some_and(|opens_at| opens_at <= now) {
+            true
+        } else {
+            continue;
+        };
+
+        // Skipped if the poll changed after it was read; the next run sees
+        // its new state.
+        match repo.apply_poll_schedule(&poll, is_active).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                log::error!(
+                    "Failed to apply schedule for poll {}: {}",
+                    poll_id.to_hex(),
+                    e
+                );
+                continue;
+            }
+        }
+
+        if poll.isactive != is_active {
+            broadcast_poll_update(PollUpdate::StatusUpdate {
+                poll_id: poll_id.to_hex(),
+                is_active,
+            })
+            .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_memory_repository::InMemoryRepository;
+    use crate::models::poll::{Poll, VotingMethod};
+    use crate::realtime::registry;
+    use crate::repositories::PollRepository;
+    use chrono::DateTime;
+    use mongodb::bson::oid::ObjectId;
+
+    fn poll(
+        isactive: bool,
+        opens_at: Option<DateTime<Utc>>,
+        closes_at: Option<DateTime<Utc>>,
+    ) -> Poll {
+        Poll {
+            id: Some(ObjectId::new()),
+            opens_at,
+            closes_at,
+            isactive,
+            ..Poll::_new(
+                "Scheduled?".to_string(),
+                vec!["a".to_string(), "b".to_string()],
+                "alice".to_string(),
+                false,
+                VotingMethod::Plurality,
+                None,
+            )
+        }
+    }
+
+    async fn stored(repo: &InMemoryRepository, poll: &Poll) -> Poll {
+        repo.get_poll_by_id(poll.id.unwrap())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn opens_and_closes_polls_when_due() {
+        let repo = InMemoryRepository::new();
+        let (now, minute) = (Utc::now(), chrono::Duration::minutes(1));
+        let opening = poll(false, Some(now - minute), Some(now + minute));
+        let closing = poll(true, None, Some(now - minute));
+        let waiting = poll(false, Some(now + minute), None);
+        for poll in [&opening, &closing, &waiting] {
+            repo.create_poll(poll.clone()).await.unwrap();
+        }
+        let mut opening_events = registry::subscribe(&opening.id.unwrap().to_hex(), None).await;
+        let mut closing_events = registry::subscribe(&closing.id.unwrap().to_hex(), None).await;
+
+        apply_schedules(&repo).await;
+
+        let opened = stored(&repo, &opening).await;
+        assert!(opened.isactive);
+        assert_eq!(
+            (opened.opens_at, opened.closes_at),
+            (None, opening.closes_at)
+        );
+        let closed = stored(&repo, &closing).await;
+        assert!(!closed.isactive);
+        assert_eq!((closed.opens_at, closed.closes_at), (None, None));
+        let waited = stored(&repo, &waiting).await;
+        assert!(!waited.isactive);
+        assert_eq!(waited.opens_at, waiting.opens_at);
+
+        for (events, expected) in [(&mut opening_events, true), (&mut closing_events, false)] {
+            let event = events.receiver.try_recv().unwrap();
+            assert!(
+                matches!(event.update, PollUpdate::StatusUpdate { is_active, .. } if is_active == expected)
+            );
+        }
+
+        // Applied schedules are cleared, so a second run changes nothing.
+        apply_schedules(&repo).await;
+        assert!(opening_events.receiver.try_recv().is_err());
+        assert!(closing_events.receiver.try_recv().is_err());
+    }
+
+    #[actix_web::test]
+    async fn leaves_polls_changed_since_they_were_read() {
+        let repo = InMemoryRepository::new();
+        let (now, minute) = (Utc::now(), chrono::Duration::minutes(1));
+        let opening = poll(false, Some(now - minute), None);
+        repo.create_poll(opening.clone()).await.unwrap();
+
+        // The owner closes the poll by hand between the read and the update.
+        let read = repo.get_scheduled_polls().await.unwrap();
+        repo.update_poll_status(opening.id.unwrap(), false)
+            .await
+            .unwrap();
+
+        assert!(!repo.apply_poll_schedule(&read[0], true).await.unwrap());
+        assert!(!stored(&repo, &opening).await.isactive);
+    }
+}
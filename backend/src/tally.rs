@@ -0,0 +1,3 @@
+pub mod irv;
+pub mod schulze;
+pub mod score;
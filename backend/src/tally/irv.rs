@@ -0,0 +1,219 @@
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct RoundTally {
+    pub option_id: String,
+    pub votes: usize,
+}
+
+#[derive(Serialize)]
+pub struct IrvRound {
+    pub round: usize,
+    // Votes for every option still in the count, in poll order.
+    pub tallies: Vec<RoundTally>,
+    // Ballots with no continuing option left on them.
+    pub exhausted: usize,
+    pub eliminated: Option<String>,
+    // True when `eliminated` was chosen by the tie-break rule.
+    pub tie_break: bool,
+}
+
+#[derive(Serialize)]
+pub struct IrvResult {
+    pub winner: Option<String>,
+    pub total_ballots: usize,
+    pub rounds: Vec<IrvRound>,
+}
+
+// Runs an instant-runoff count over ranked ballots.
+//
+// Each round, every ballot counts for its highest-ranked option that has not
+// been eliminated. An option wins once it holds more than half of the
+// non-exhausted ballots, or when it is the only option left. Otherwise the
+// option with the fewest votes is eliminated and the next round begins.
+//
+// Ties for last place are broken deterministically:
+// 1. the tied option with fewer votes in the most recent earlier round where
+//    the tied options differ is eliminated;
+// 2. if they were tied in every earlier round, the option listed last in the
+//    poll definition is eliminated.
+//
+// If no ballot counts for any option, the count stops with no winner.
+pub fn tally(candidates: &[ObjectId], ballots: &[Vec<ObjectId>]) -> IrvResult {
+    let mut continuing: Vec<usize> = (0..candidates.len()).collect();
+    let mut history: Vec<Vec<usize>> = Vec::new();
+    let mut rounds = Vec::new();
+    let mut winner = None;
+
+    loop {
+        let mut counts = vec![0usize; candidates.len()];
+        let mut exhausted = 0;
+
+        for ballot in ballots {
+            let choice = ballot.iter().find_map(|option_id| {
+                continuing
+                    .iter()
+                    .copied()
+                    .find(|&index| candidates[index] == *option_id)
+            });
+
+            match choice {
+                Some(index) => counts[index] += 1,
+                None => exhausted += 1,
+            }
+        }
+
+        let active = ballots.len() - exhausted;
+        let tallies = continuing
+            .iter()
+            .map(|&index| RoundTally {
+                option_id: candidates[index].to_hex(),
+                votes: counts[index],
+            })
+            .collect();
+        history.push(counts);
+
+        let mut round = IrvRound {
+            round: rounds.len() + 1,
+            tallies,
+            exhausted,
+            eliminated: None,
+            tie_break: false,
+        };
+
+        if continuing.is_empty() || active == 0 {
+            rounds.push(round);
+            break;
+        }
+
+        let current = &history[history.len() - 1];
+        let leader = continuing
+            .iter()
+            .copied()
+            .max_by_key(|&index| current[index])
+            .unwrap_or_default();
+
+        if continuing.len() == 1 || current[leader] * 2 > active {
+            winner = Some(candidates[leader].to_hex());
+            rounds.push(round);
+            break;
+        }
+
+        let (eliminated, tie_break) = last_place(&continuing, &history);
+        continuing.retain(|&index| index != eliminated);
+
+        round.eliminated = Some(candidates[eliminated].to_hex());
+        round.tie_break = tie_break;
+        rounds.push(round);
+    }
+
+    IrvResult {
+        winner,
+        total_ballots: ballots.len(),
+        rounds,
+    }
+}
+
+// Picks the option to eliminate from the latest round in `history`, returning
+// whether a tie-break was needed.
+fn last_place(continuing: &[usize], history: &[Vec<usize>]) -> (usize, bool) {
+    let lowest_in = |round: &Vec<usize>, among: &[usize]| -> Vec<usize> {
+        let min = among.iter().map(|&index| round[index]).min().unwrap_or(0);
+        among
+            .iter()
+            .copied()
+            .filter(|&index| round[index] == min)
+            .collect()
+    };
+
+    let mut tied = lowest_in(&history[history.len() - 1], continuing);
+    let tie_break = tied.len() > 1;
+
+    for round in history.iter().rev().skip(1) {
+        if tied.len() == 1 {
+            break;
+        }
+        tied = lowest_in(round, &tied);
+    }
+
+    (tied.into_iter().max().unwrap_or_default(), tie_break)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(n: usize) -> Vec<ObjectId> {
+        (0..n).map(|_| ObjectId::new()).collect()
+    }
+
+    fn ballots(rankings: &[(&[ObjectId], usize)]) -> Vec<Vec<ObjectId>> {
+        rankings
+            .iter()
+            .flat_map(|(ranking, copies)| std::iter::repeat_n(ranking.to_vec(), *copies))
+            .collect()
+    }
+
+    #[test]
+    fn majority_wins_in_the_first_round() {
+        let o = options(3);
+        let result = tally(&o, &ballots(&[(&[o[0]], 2), (&[o[1]], 1)]));
+
+        assert_eq!(result.winner, Some(o[0].to_hex()));
+        assert_eq!(result.total_ballots, 3);
+        assert_eq!(result.rounds.len(), 1);
+    }
+
+    #[test]
+    fn eliminated_ballots_transfer_to_their_next_choice() {
+        let o = options(3);
+        let result = tally(
+            &o,
+            &ballots(&[(&[o[0]], 2), (&[o[1]], 2), (&[o[2], o[1]], 1)]),
+        );
+
+        assert_eq!(result.rounds[0].eliminated, Some(o[2].to_hex()));
+        assert!(!result.rounds[0].tie_break);
+        assert_eq!(result.rounds[1].tallies[1].votes, 3);
+        assert_eq!(result.winner, Some(o[1].to_hex()));
+    }
+
+    #[test]
+    fn exhausted_ballots_leave_the_count() {
+        let o = options(3);
+        let result = tally(&o, &ballots(&[(&[o[0]], 2), (&[o[1]], 1), (&[o[2]], 1)]));
+
+        // b and c tie with no earlier round, so the one listed last goes.
+        assert_eq!(result.rounds[0].eliminated, Some(o[2].to_hex()));
+        assert!(result.rounds[0].tie_break);
+        assert_eq!(result.rounds[1].exhausted, 1);
+        assert_eq!(result.winner, Some(o[0].to_hex()));
+    }
+
+    #[test]
+    fn ties_are_broken_by_the_latest_round_that_differs() {
+        // Listed a, c, b, d so that poll order alone would eliminate b.
+        let o = options(4);
+        let (a, b, c, d) = (o[0], o[1], o[2], o[3]);
+        let result = tally(
+            &[a, c, b, d],
+            &ballots(&[(&[a], 4), (&[b], 3), (&[c], 2), (&[d, c], 1)]),
+        );
+
+        assert_eq!(result.rounds[0].eliminated, Some(d.to_hex()));
+        // b and c both hold 3 votes, but c had fewer in the first round.
+        assert_eq!(result.rounds[1].eliminated, Some(c.to_hex()));
+        assert!(result.rounds[1].tie_break);
+        assert_eq!(result.winner, Some(a.to_hex()));
+    }
+
+    #[test]
+    fn no_ballots_means_no_winner() {
+        let o = options(2);
+        let result = tally(&o, &[]);
+
+        assert_eq!(result.winner, None);
+        assert_eq!(result.rounds.len(), 1);
+    }
+}
@@ -0,0 +1,170 @@
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct SchulzeResult {
+    // Row and column order of both matrices.
+    pub options: Vec<String>,
+    pub total_ballots: usize,
+    // `pairwise[i][j]` is the number of ballots ranking option i above option j.
+    pub pairwise: Vec<Vec<usize>>,
+    // `strongest_paths[i][j]` is the strength of the strongest path from i to j.
+    pub strongest_paths: Vec<Vec<usize>>,
+    // Options in tiers from best to worst. No option is beaten by one in its
+    // own or a later tier, so options sharing a tier either tie or aren't
+    // ordered relative to each other by the ballots.
+    pub ranking: Vec<Vec<String>>,
+}
+
+// Tallies ranked ballots with the Schulze method.
+//
+// A ballot ranks an option above every option listed after it and above every
+// option it leaves out; options left out are not ranked against each other.
+// Options keep their poll order inside a ranking tier.
+pub fn tally(candidates: &[ObjectId], ballots: &[Vec<ObjectId>]) -> SchulzeResult {
+    let n = candidates.len();
+    let mut pairwise = vec![vec![0usize; n]; n];
+
+    for ballot in ballots {
+        let positions: Vec<Option<usize>> = candidates
+            .iter()
+            .map(|candidate| ballot.iter().position(|option_id| option_id == candidate))
+            .collect();
+
+        for i in 0..n {
+            for j in 0..n {
+                let prefers = match (positions[i], positions[j]) {
+                    (Some(a), Some(b)) => a < b,
+                    (Some(_), None) => true,
+                    _ => false,
+                };
+                if i != j && prefers {
+                    pairwise[i][j] += 1;
+                }
+            }
+        }
+    }
+
+    let mut strongest_paths = vec![vec![0usize; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            if i != j && pairwise[i][j] > pairwise[j][i] {
+                strongest_paths[i][j] = pairwise[i][j];
+            }
+        }
+    }
+
+    for i in 0..n {
+        for j in 0..n {
+            if i == j {
+                continue;
+            }
+            for k in 0..n {
+                if k != i && k != j {
+                    let through_i = strongest_paths[j][i].min(strongest_paths[i][k]);
+                    if through_i > strongest_paths[j][k] {
+                        strongest_paths[j][k] = through_i;
+                    }
+                }
+            }
+        }
+    }
+
+    // The Schulze relation is a strict partial order, not a ranking: two
+    // options can each be unbeaten by the other yet compare differently with a
+    // third. Peeling off the options nothing remaining beats, layer by layer,
+    // never places an option level with or above one that beats it.
+    let beats = |i: usize, j: usize| strongest_paths[i][j] > strongest_paths[j][i];
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut ranking: Vec<Vec<String>> = Vec::new();
+    while !remaining.is_empty() {
+        let (tier, rest): (Vec<usize>, Vec<usize>) = remaining
+            .iter()
+            .partition(|&&i| !remaining.iter().any(|&j| beats(j, i)));
+        ranking.push(tier.iter().map(|&i| candidates[i].to_hex()).collect());
+        remaining = rest;
+    }
+
+    SchulzeResult {
+        options: candidates.iter().map(|id| id.to_hex()).collect(),
+        total_ballots: ballots.len(),
+        pairwise,
+        strongest_paths,
+        ranking,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_options_by_pairwise_strength() {
+        let (a, b, c) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let ballots = vec![vec![a, b, c], vec![a, b, c], vec![b, a, c]];
+        let result = tally(&[a, b, c], &ballots);
+
+        assert_eq!(result.total_ballots, 3);
+        assert_eq!(result.pairwise[0][1], 2);
+        assert_eq!(result.pairwise[1][0], 1);
+        assert_eq!(result.pairwise[0][2], 3);
+        assert_eq!(
+            result.ranking,
+            vec![vec![a.to_hex()], vec![b.to_hex()], vec![c.to_hex()]]
+        );
+    }
+
+    #[test]
+    fn unranked_options_lose_to_ranked_ones_but_not_to_each_other() {
+        let (a, b, c) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let result = tally(&[a, b, c], &[vec![a]]);
+
+        assert_eq!(result.pairwise[0][1], 1);
+        assert_eq!(result.pairwise[1][2], 0);
+        assert_eq!(result.pairwise[2][1], 0);
+        assert_eq!(
+            result.ranking,
+            vec![vec![a.to_hex()], vec![b.to_hex(), c.to_hex()]]
+        );
+    }
+
+    #[test]
+    fn resolves_a_cycle_by_strongest_paths() {
+        // a beats b 6-3, b beats c 7-2, c beats a 5-4; c's win over a is the
+        // weakest link in the cycle, so a comes out ahead of c.
+        let (a, b, c) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let mut ballots = vec![vec![a, b, c]; 4];
+        ballots.extend(vec![vec![b, c, a]; 3]);
+        ballots.extend(vec![vec![c, a, b]; 2]);
+        let result = tally(&[a, b, c], &ballots);
+
+        assert_eq!(result.pairwise[0][1], 6);
+        assert_eq!(result.pairwise[1][2], 7);
+        assert_eq!(result.pairwise[2][0], 5);
+        assert_eq!(
+            result.ranking,
+            vec![vec![a.to_hex()], vec![b.to_hex()], vec![c.to_hex()]]
+        );
+    }
+
+    #[test]
+    fn unordered_options_share_the_highest_tier_they_can() {
+        // a beats b beats c, while d ties each of them head to head: d beats
+        // nothing, like c, but nothing beats d either.
+        let (a, b, c, d) = (ObjectId::new(), ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let result = tally(&[a, b, c, d], &[vec![a, b, c, d], vec![d, a, b, c]]);
+
+        assert_eq!(
+            result.ranking,
+            vec![vec![a.to_hex(), d.to_hex()], vec![b.to_hex()], vec![c.to_hex()]]
+        );
+    }
+
+    #[test]
+    fn opposite_ballots_tie_in_poll_order() {
+        let (a, b) = (ObjectId::new(), ObjectId::new());
+        let result = tally(&[a, b], &[vec![a, b], vec![b, a]]);
+
+        assert_eq!(result.ranking, vec![vec![a.to_hex(), b.to_hex()]]);
+    }
+}
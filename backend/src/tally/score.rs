@@ -0,0 +1,149 @@
+use crate::models::poll::ScoreRange;
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct ScoreBucket {
+    pub score: i32,
+    pub count: usize,
+}
+
+#[derive(Serialize)]
+pub struct OptionScore {
+    pub option_id: String,
+    // Number of ballots that rated this option.
+    pub count: usize,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    // One bucket per score in the poll's range, lowest first.
+    pub distribution: Vec<ScoreBucket>,
+}
+
+#[derive(Serialize)]
+pub struct ScoreResult {
+    pub total_ballots: usize,
+    pub min: i32,
+    pub max: i32,
+    pub options: Vec<OptionScore>,
+}
+
+// Summarises score ballots per option, in poll order. Options a ballot did
+// not rate are left out of that option's statistics rather than counted as
+// the minimum score.
+pub fn tally(
+    candidates: &[ObjectId],
+    range: ScoreRange,
+    ballots: &[Vec<(ObjectId, i32)>],
+) -> ScoreResult {
+    let options = candidates
+        .iter()
+        .map(|candidate| {
+            let mut scores: Vec<i32> = ballots
+                .iter()
+                .flat_map(|ballot| ballot.iter())
+                .filter(|(option_id, _)| option_id == candidate)
+                .map(|(_, score)| *score)
+                .collect();
+            scores.sort_unstable();
+
+            let count = scores.len();
+            let mean = (count > 0)
+                .then(|| scores.iter().map(|&score| f64::from(score)).sum::<f64>() / count as f64);
+            let median = match count {
+                0 => None,
+                _ if count % 2 == 1 => Some(f64::from(scores[count / 2])),
+                _ => Some((f64::from(scores[count / 2 - 1]) + f64::from(scores[count / 2])) / 2.0),
+            };
+            // A range too wide to bucket, from before ranges were capped,
+            // only gets buckets for the scores actually given.
+            let buckets: Vec<i32> = if range.is_valid() {
+                (range.min..=range.max).collect()
+            } else {
+                let mut given = scores.clone();
+                given.dedup();
+                given
+            };
+            let distribution = buckets
+                .into_iter()
+                .map(|score| ScoreBucket {
+                    score,
+                    count: scores.iter().filter(|&&s| s == score).count(),
+                })
+                .collect();
+
+            OptionScore {
+                option_id: candidate.to_hex(),
+                count,
+                mean,
+                median,
+                distribution,
+            }
+        })
+        .collect();
+
+    ScoreResult {
+        total_ballots: ballots.len(),
+        min: range.min,
+        max: range.max,
+        options,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(option: &OptionScore) -> Vec<(i32, usize)> {
+        option
+            .distribution
+            .iter()
+            .map(|bucket| (bucket.score, bucket.count))
+            .collect()
+    }
+
+    #[test]
+    fn summarises_each_option() {
+        let (a, b, c) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let range = ScoreRange { min: 1, max: 3 };
+        let ballots = vec![vec![(a, 1), (b, 3)], vec![(a, 3)], vec![(a, 2)]];
+        let result = tally(&[a, b, c], range, &ballots);
+
+        assert_eq!(result.total_ballots, 3);
+        let option_a = &result.options[0];
+        assert_eq!(option_a.count, 3);
+        assert_eq!(option_a.mean, Some(2.0));
+        assert_eq!(option_a.median, Some(2.0));
+        assert_eq!(counts(option_a), vec![(1, 1), (2, 1), (3, 1)]);
+        assert_eq!(result.options[1].mean, Some(3.0));
+    }
+
+    #[test]
+    fn unrated_options_have_no_statistics() {
+        let (a, b) = (ObjectId::new(), ObjectId::new());
+        let result = tally(&[a, b], ScoreRange::default(), &[vec![(a, 4)]]);
+
+        let option_b = &result.options[1];
+        assert_eq!(option_b.count, 0);
+        assert_eq!(option_b.mean, None);
+        assert_eq!(option_b.median, None);
+        assert_eq!(option_b.distribution.len(), 6);
+    }
+
+    #[test]
+    fn median_of_an_even_count_is_the_midpoint() {
+        let a = ObjectId::new();
+        let result = tally(&[a], ScoreRange::default(), &[vec![(a, 1)], vec![(a, 2)]]);
+
+        assert_eq!(result.options[0].median, Some(1.5));
+    }
+
+    #[test]
+    fn ranges_too_wide_only_bucket_given_scores() {
+        let a = ObjectId::new();
+        let range = ScoreRange { min: 0, max: 1000 };
+        let ballots = vec![vec![(a, 5)], vec![(a, 5)], vec![(a, 7)]];
+        let result = tally(&[a], range, &ballots);
+
+        assert_eq!(counts(&result.options[0]), vec![(5, 2), (7, 1)]);
+    }
+}
@@ -1,2 +1,6 @@
 pub mod db;
-pub mod jwt;
\ No newline at end of file
+pub mod instance;
+pub mod jwt;
+pub mod password;
+pub mod oidc;
+pub mod session;
\ No newline at end of file
@@ -1,3 +1,5 @@
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::bson::doc;
 use mongodb::{options::ClientOptions, Client};
 use std::env;
 
@@ -10,3 +12,19 @@ pub async fn _get_database_client() -> mongodb::error::Result<Client> {
 
     Client::with_options(client_options)
 }
+
+// Multi-document transactions need a replica set or a sharded cluster.
+pub async fn _supports_transactions(client: &Client) -> mongodb::error::Result<bool> {
+    let hello = client.database("admin").run_command(doc! { "hello": 1 }).await?;
+    Ok(hello.contains_key("setName") || hello.get_str("msg") == Ok("isdbgrid"))
+}
+
+// E11000, reported as a write error by inserts and updates and as a command
+// error by findAndModify.
+pub fn _is_duplicate_key_error(error: &mongodb::error::Error) -> bool {
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(e)) => e.code == 11000,
+        ErrorKind::Command(e) => e.code == 11000,
+        _ => false,
+    }
+}
@@ -0,0 +1,10 @@
+use mongodb::bson::oid::ObjectId;
+use once_cell::sync::Lazy;
+
+static INSTANCE_ID: Lazy<String> = Lazy::new(|| ObjectId::new().to_hex());
+
+// Identifies this process among the replicas sharing a database. A new one is
+// picked on every start.
+pub fn _instance_id() -> &'static str {
+    &INSTANCE_ID
+}
@@ -1,36 +1,57 @@
+use crate::repositories::Repository;
 use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey};
 use serde::{Deserialize, Serialize};
 use chrono::{Utc, Duration};
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Claims {
-    sub: String,
-    exp: usize,
+pub struct Claims {
+    pub sub: String,
+    pub exp: usize,
+    pub jti: String,
 }
 
-const _EXPIRATION_HOURS: i64 = 24;
+#[derive(Debug)]
+pub enum JwtError {
+    Invalid,
+    Revoked,
+    Storage,
+}
+
+pub const _EXPIRATION_MINUTES: i64 = 15;
 
 pub fn _create_jwt(user_id: &str) -> Result<String, jsonwebtoken::errors::Error> {
     let expiration = Utc::now()
-        .checked_add_signed(Duration::hours(_EXPIRATION_HOURS))
+        .checked_add_signed(Duration::minutes(_EXPIRATION_MINUTES))
         .expect("Invalid time")
         .timestamp() as usize;
 
     let claims = Claims {
         sub: user_id.to_owned(),
         exp: expiration,
+        jti: crate::utils::session::_random_token(),
     };
 
     let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
     encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_ref()))
 }
 
-pub fn _verify_jwt(token: &str) -> Result<String, jsonwebtoken::errors::Error> {
+// Checks the signature and expiry only; use `_verify_jwt` to authenticate.
+pub fn _decode_jwt(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
     let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
     let token_data = decode::<Claims>(
         token,
         &DecodingKey::from_secret(secret.as_ref()),
         &Validation::default(),
     )?;
-    Ok(token_data.claims.sub)
+    Ok(token_data.claims)
+}
+
+pub async fn _verify_jwt(token: &str, repo: &dyn Repository) -> Result<String, JwtError> {
+    let claims = _decode_jwt(token).map_err(|_| JwtError::Invalid)?;
+
+    match repo.is_access_token_revoked(&claims.jti).await {
+        Ok(false) => Ok(claims.sub),
+        Ok(true) => Err(JwtError::Revoked),
+        Err(_) => Err(JwtError::Storage),
+    }
 }
@@ -0,0 +1,30 @@
+use argon2::password_hash::{
+    rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+};
+use argon2::Argon2;
+use once_cell::sync::Lazy;
+
+// Verified against when a user has no credential, so a failed login takes as
+// long whether or not the user ID exists.
+static DUMMY_HASH: Lazy<String> =
+    Lazy::new(|| _hash_password("dummy password").unwrap_or_default());
+
+pub fn _hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+pub fn _verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed_hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+// Spends the time of a verification without anything to verify against.
+pub fn _verify_dummy_password(password: &str) {
+    let _ = _verify_password(password, &DUMMY_HASH);
+}
@@ -0,0 +1,129 @@
+use crate::models::token::{RefreshToken, RevokedToken};
+use crate::repositories::Repository;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+
+pub const _REFRESH_TOKEN_DAYS: i64 = 30;
+
+#[derive(Serialize)]
+pub struct TokenPair {
+    pub token: String,
+    pub refresh_token: String,
+    // Lifetime of `token` in seconds.
+    pub expires_in: i64,
+}
+
+#[derive(Debug)]
+pub enum RefreshError {
+    Invalid,
+    Expired,
+    // The token had already been rotated; its whole family is now revoked.
+    Reused,
+    Internal,
+}
+
+pub fn _random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn hash_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+// Issues an access token and a refresh token starting a new token family.
+pub async fn _issue_tokens(
+    repo: &dyn Repository,
+    user_id: &str,
+) -> Result<TokenPair, Box<dyn Error>> {
+    issue_in_family(repo, user_id, _random_token()).await
+}
+
+async fn issue_in_family(
+    repo: &dyn Repository,
+    user_id: &str,
+    family_id: String,
+) -> Result<TokenPair, Box<dyn Error>> {
+    let token = crate::utils::jwt::_create_jwt(user_id)?;
+    let refresh_token = _random_token();
+
+    repo.store_refresh_token(RefreshToken::_new(
+        hash_token(&refresh_token),
+        family_id,
+        user_id.to_string(),
+        Utc::now() + Duration::days(_REFRESH_TOKEN_DAYS),
+    ))
+    .await?;
+
+    Ok(TokenPair {
+        token,
+        refresh_token,
+        expires_in: crate::utils::jwt::_EXPIRATION_MINUTES * 60,
+    })
+}
+
+// Exchanges a refresh token for a new token pair in the same family. A
+// refresh token can be used once; presenting it again revokes the family.
+pub async fn _rotate_refresh_token(
+    repo: &dyn Repository,
+    refresh_token: &str,
+) -> Result<TokenPair, RefreshError> {
+    let token_hash = hash_token(refresh_token);
+
+    let stored = match repo.find_refresh_token(&token_hash).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return Err(RefreshError::Invalid),
+        Err(_) => return Err(RefreshError::Internal),
+    };
+
+    if stored.revoked {
+        return Err(RefreshError::Invalid);
+    }
+
+    if stored.expires_at <= Utc::now() {
+        return Err(RefreshError::Expired);
+    }
+
+    match repo.mark_refresh_token_used(&token_hash).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return match repo.revoke_token_family(&stored.family_id).await {
+                Ok(_) => Err(RefreshError::Reused),
+                Err(_) => Err(RefreshError::Internal),
+            };
+        }
+        Err(_) => return Err(RefreshError::Internal),
+    }
+
+    issue_in_family(repo, &stored.user_id, stored.family_id)
+        .await
+        .map_err(|_| RefreshError::Internal)
+}
+
+// Revokes the family of a refresh token. Unknown tokens are ignored.
+pub async fn _revoke_refresh_token(
+    repo: &dyn Repository,
+    refresh_token: &str,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(stored) = repo.find_refresh_token(&hash_token(refresh_token)).await? {
+        repo.revoke_token_family(&stored.family_id).await?;
+    }
+    Ok(())
+}
+
+// Revokes an access token until it would have expired anyway.
+pub async fn _revoke_access_token(
+    repo: &dyn Repository,
+    token: &str,
+) -> Result<(), Box<dyn Error>> {
+    let claims = crate::utils::jwt::_decode_jwt(token)?;
+    let expires_at = DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or_else(Utc::now);
+    repo.revoke_access_token(RevokedToken::_new(claims.jti, expires_at))
+        .await
+}
@@ -0,0 +1,377 @@
+use crate::models::poll::{Poll, VotingMethod};
+use crate::repositories::Repository;
+use actix_web::HttpResponse;
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use std::collections::HashSet;
+
+// Reasons a ballot can be refused. Each maps to a 4xx status and a stable
+// `error` code so clients can tell the user why their vote was rejected.
+#[derive(Debug)]
+pub enum VoteValidationError {
+    InvalidPollId,
+    InvalidOptionId(String),
+    PollNotFound,
+    PollClosed,
+    EmptySelection,
+    DuplicateOption(String),
+    UnknownOption(String),
+    TooManyOptions,
+    ScoresNotAccepted,
+    OptionIdsNotAccepted,
+    ScoreOutOfRange { option_id: String, min: i32, max: i32 },
+    InvalidScoreRange,
+    Internal,
+}
+
+impl VoteValidationError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            VoteValidationError::InvalidPollId => "invalid_poll_id",
+            VoteValidationError::InvalidOptionId(_) => "invalid_option_id",
+            VoteValidationError::PollNotFound => "poll_not_found",
+            VoteValidationError::PollClosed => "poll_closed",
+            VoteValidationError::EmptySelection => "empty_selection",
+            VoteValidationError::DuplicateOption(_) => "duplicate_option",
+            VoteValidationError::UnknownOption(_) => "unknown_option",
+            VoteValidationError::TooManyOptions => "too_many_options",
+            VoteValidationError::ScoresNotAccepted => "scores_not_accepted",
+            VoteValidationError::OptionIdsNotAccepted => "option_ids_not_accepted",
+            VoteValidationError::ScoreOutOfRange { .. } => "score_out_of_range",
+            VoteValidationError::InvalidScoreRange => "invalid_score_range",
+            VoteValidationError::Internal => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            VoteValidationError::InvalidPollId => "Invalid poll ID format".to_string(),
+            VoteValidationError::InvalidOptionId(id) => format!("Invalid option ID format: {}", id),
+            VoteValidationError::PollNotFound => "Poll not found".to_string(),
+            VoteValidationError::PollClosed => "This poll is closed for voting".to_string(),
+            VoteValidationError::EmptySelection => "At least one option must be selected".to_string(),
+            VoteValidationError::DuplicateOption(id) => format!("Option {} was selected more than once", id),
+            VoteValidationError::UnknownOption(id) => format!("Option {} does not belong to this poll", id),
+            VoteValidationError::TooManyOptions => "This poll only allows a single choice".to_string(),
+            VoteValidationError::ScoresNotAccepted => "This poll does not accept scores".to_string(),
+            VoteValidationError::OptionIdsNotAccepted => {
+                "This poll expects a score per option instead of option IDs".to_string()
+            }
+            VoteValidationError::ScoreOutOfRange { option_id, min, max } => {
+                format!("Score for option {} must be between {} and {}", option_id, min, max)
+            }
+            VoteValidationError::InvalidScoreRange => {
+                "This poll's score range is too wide to accept votes".to_string()
+            }
+            VoteValidationError::Internal => "Failed to validate vote".to_string(),
+        }
+    }
+
+    pub fn to_response(&self) -> HttpResponse {
+        let mut response = match self {
+            VoteValidationError::InvalidPollId | VoteValidationError::InvalidOptionId(_) => {
+                HttpResponse::BadRequest()
+            }
+            VoteValidationError::PollNotFound => HttpResponse::NotFound(),
+            VoteValidationError::PollClosed => HttpResponse::Forbidden(),
+            VoteValidationError::EmptySelection
+            | VoteValidationError::DuplicateOption(_)
+            | VoteValidationError::UnknownOption(_)
+            | VoteValidationError::TooManyOptions
+            | VoteValidationError::ScoresNotAccepted
+            | VoteValidationError::OptionIdsNotAccepted
+            | VoteValidationError::ScoreOutOfRange { .. }
+            | VoteValidationError::InvalidScoreRange => HttpResponse::UnprocessableEntity(),
+            VoteValidationError::Internal => HttpResponse::InternalServerError(),
+        };
+
+        response.json(serde_json::json!({
+            "error": self.code(),
+            "message": self.message(),
+        }))
+    }
+}
+
+// A ballot as submitted, before any of its IDs have been parsed.
+pub struct SubmittedVote {
+    pub poll_id: String,
+    pub option_ids: Vec<String>,
+    // `(option_id, score)` pairs for score polls.
+    pub scores: Vec<(String, i32)>,
+}
+
+// A ballot that has been checked against its poll definition.
+pub struct ValidatedVote {
+    pub poll_id: ObjectId,
+    pub option_ids: Vec<ObjectId>,
+    pub scores: Vec<(ObjectId, i32)>,
+}
+
+// Loads the poll and checks the submitted ballot against it.
+pub async fn validate_vote(
+    repo: &dyn Repository,
+    vote_data: &SubmittedVote,
+) -> Result<ValidatedVote, VoteValidationError> {
+    let poll_id = ObjectId::parse_str(&vote_data.poll_id)
+        .map_err(|_| VoteValidationError::InvalidPollId)?;
+
+    let option_ids = vote_data
+        .option_ids
+        .iter()
+        .map(|id| parse_option_id(id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let scores = vote_data
+        .scores
+        .iter()
+        .map(|(id, score)| parse_option_id(id).map(|id| (id, *score)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let poll = match repo.get_poll_by_id(poll_id).await {
+        Ok(Some(poll)) => poll,
+        Ok(None) => return Err(VoteValidationError::PollNotFound),
+        Err(_) => return Err(VoteValidationError::Internal),
+    };
+
+    // The scheduler may not have flipped `isactive` yet, so the schedule is
+    // checked directly as well.
+    if !poll.isactive || !poll.is_within_schedule(Utc::now()) {
+        return Err(VoteValidationError::PollClosed);
+    }
+
+    if poll.voting_method == VotingMethod::Score {
+        if !option_ids.is_empty() {
+            return Err(VoteValidationError::OptionIdsNotAccepted);
+        }
+
+        let scored_ids: Vec<ObjectId> = scores.iter().map(|(id, _)| *id).collect();
+        check_options(&poll, &scored_ids)?;
+
+        // Polls created before ranges were capped may still be too wide.
+        let range = poll.score_range.unwrap_or_default();
+        if !range.is_valid() {
+            return Err(VoteValidationError::InvalidScoreRange);
+        }
+        for (option_id, score) in &scores {
+            if *score < range.min || *score > range.max {
+                return Err(VoteValidationError::ScoreOutOfRange {
+                    option_id: option_id.to_hex(),
+                    min: range.min,
+                    max: range.max,
+                });
+            }
+        }
+
+        // Scored options are also recorded as selected so the plain tally
+        // reports how many ballots rated each option.
+        return Ok(ValidatedVote {
+            poll_id,
+            option_ids: scored_ids,
+            scores,
+        });
+    }
+
+    if !scores.is_empty() {
+        return Err(VoteValidationError::ScoresNotAccepted);
+    }
+
+    check_options(&poll, &option_ids)?;
+
+    // A ranked ballot may rank any number of options, so only plurality polls
+    // are limited by `is_multiple_choice`.
+    if poll.voting_method == VotingMethod::Plurality
+        && !poll.is_multiple_choice
+        && option_ids.len() > 1
+    {
+        return Err(VoteValidationError::TooManyOptions);
+    }
+
+    Ok(ValidatedVote {
+        poll_id,
+        option_ids,
+        scores,
+    })
+}
+
+fn parse_option_id(id: &str) -> Result<ObjectId, VoteValidationError> {
+    ObjectId::parse_str(id).map_err(|_| VoteValidationError::InvalidOptionId(id.to_string()))
+}
+
+// Rejects empty ballots and options that are repeated or not in the poll.
+fn check_options(poll: &Poll, option_ids: &[ObjectId]) -> Result<(), VoteValidationError> {
+    if option_ids.is_empty() {
+        return Err(VoteValidationError::EmptySelection);
+    }
+
+    let mut seen = HashSet::new();
+    for option_id in option_ids {
+        if !seen.insert(*option_id) {
+            return Err(VoteValidationError::DuplicateOption(option_id.to_hex()));
+        }
+        if !poll.options.iter().any(|(id, _)| id == option_id) {
+            return Err(VoteValidationError::UnknownOption(option_id.to_hex()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_memory_repository::InMemoryRepository;
+    use crate::models::poll::ScoreRange;
+    use crate::repositories::PollRepository;
+
+    async fn repo_with(poll: Poll) -> (InMemoryRepository, Poll) {
+        let poll = Poll {
+            id: Some(ObjectId::new()),
+            ..poll
+        };
+        let repo = InMemoryRepository::new();
+        repo.create_poll(poll.clone()).await.unwrap();
+        (repo, poll)
+    }
+
+    fn new_poll(method: VotingMethod, multiple: bool) -> Poll {
+        let options = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let range = (method == VotingMethod::Score).then_some(ScoreRange { min: 1, max: 5 });
+        Poll::_new(
+            "q".to_string(),
+            options,
+            "alice".to_string(),
+            multiple,
+            method,
+            range,
+        )
+    }
+
+    fn ballot(poll: &Poll, options: &[usize]) -> SubmittedVote {
+        SubmittedVote {
+            poll_id: poll.id.unwrap().to_hex(),
+            option_ids: options
+                .iter()
+                .map(|&i| poll.options[i].0.to_hex())
+                .collect(),
+            scores: Vec::new(),
+        }
+    }
+
+    fn scored(poll: &Poll, scores: &[(usize, i32)]) -> SubmittedVote {
+        SubmittedVote {
+            poll_id: poll.id.unwrap().to_hex(),
+            option_ids: Vec::new(),
+            scores: scores
+                .iter()
+                .map(|&(i, score)| (poll.options[i].0.to_hex(), score))
+                .collect(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn accepts_a_valid_ballot() {
+        let (repo, poll) = repo_with(new_poll(VotingMethod::RankedChoice, false)).await;
+        let vote = validate_vote(&repo, &ballot(&poll, &[2, 0])).await.unwrap();
+
+        assert_eq!(vote.poll_id, poll.id.unwrap());
+        assert_eq!(vote.option_ids, vec![poll.options[2].0, poll.options[0].0]);
+    }
+
+    #[actix_web::test]
+    async fn rejects_malformed_ids() {
+        let (repo, poll) = repo_with(new_poll(VotingMethod::Plurality, false)).await;
+
+        let mut vote = ballot(&poll, &[0]);
+        vote.poll_id = "nope".to_string();
+        let result = validate_vote(&repo, &vote).await;
+        assert!(matches!(result, Err(VoteValidationError::InvalidPollId)));
+
+        let mut vote = ballot(&poll, &[0]);
+        vote.option_ids.push("nope".to_string());
+        let result = validate_vote(&repo, &vote).await;
+        assert!(matches!(
+            result,
+            Err(VoteValidationError::InvalidOptionId(_))
+        ));
+    }
+
+    #[actix_web::test]
+    async fn rejects_missing_and_closed_polls() {
+        let (repo, mut poll) = repo_with(new_poll(VotingMethod::Plurality, false)).await;
+
+        let mut vote = ballot(&poll, &[0]);
+        vote.poll_id = ObjectId::new().to_hex();
+        let result = validate_vote(&repo, &vote).await;
+        assert!(matches!(result, Err(VoteValidationError::PollNotFound)));
+
+        poll.isactive = false;
+        let (repo, poll) = repo_with(poll).await;
+        let result = validate_vote(&repo, &ballot(&poll, &[0])).await;
+        assert!(matches!(result, Err(VoteValidationError::PollClosed)));
+    }
+
+    #[actix_web::test]
+    async fn checks_the_selected_options() {
+        let (repo, poll) = repo_with(new_poll(VotingMethod::Plurality, false)).await;
+
+        let result = validate_vote(&repo, &ballot(&poll, &[])).await;
+        assert!(matches!(result, Err(VoteValidationError::EmptySelection)));
+
+        let result = validate_vote(&repo, &ballot(&poll, &[0, 1])).await;
+        assert!(matches!(result, Err(VoteValidationError::TooManyOptions)));
+
+        let mut vote = ballot(&poll, &[0]);
+        vote.option_ids.push(ObjectId::new().to_hex());
+        let result = validate_vote(&repo, &vote).await;
+        assert!(matches!(result, Err(VoteValidationError::UnknownOption(_))));
+
+        let (repo, poll) = repo_with(new_poll(VotingMethod::Plurality, true)).await;
+        let result = validate_vote(&repo, &ballot(&poll, &[1, 1])).await;
+        assert!(matches!(
+            result,
+            Err(VoteValidationError::DuplicateOption(_))
+        ));
+    }
+
+    #[actix_web::test]
+    async fn checks_scores_against_the_range() {
+        let (repo, poll) = repo_with(new_poll(VotingMethod::Score, false)).await;
+
+        let vote = validate_vote(&repo, &scored(&poll, &[(0, 1), (1, 5)]))
+            .await
+            .unwrap();
+        assert_eq!(vote.option_ids, vec![poll.options[0].0, poll.options[1].0]);
+
+        let result = validate_vote(&repo, &scored(&poll, &[(0, 6)])).await;
+        assert!(matches!(
+            result,
+            Err(VoteValidationError::ScoreOutOfRange { .. })
+        ));
+
+        let result = validate_vote(&repo, &ballot(&poll, &[0])).await;
+        assert!(matches!(
+            result,
+            Err(VoteValidationError::OptionIdsNotAccepted)
+        ));
+
+        let (repo, plurality) = repo_with(new_poll(VotingMethod::Plurality, false)).await;
+        let result = validate_vote(&repo, &scored(&plurality, &[(0, 1)])).await;
+        assert!(matches!(
+            result,
+            Err(VoteValidationError::ScoresNotAccepted)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn refuses_votes_on_ranges_too_wide() {
+        let mut wide = new_poll(VotingMethod::Score, false);
+        wide.score_range = Some(ScoreRange { min: 0, max: 1000 });
+        let (repo, poll) = repo_with(wide).await;
+
+        let result = validate_vote(&repo, &scored(&poll, &[(0, 1)])).await;
+        assert!(matches!(
+            result,
+            Err(VoteValidationError::InvalidScoreRange)
+        ));
+    }
+}
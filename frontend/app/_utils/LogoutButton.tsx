@@ -7,8 +7,21 @@ export default function LogoutButton() {
     const { logout } = useCorbado()
     const router = useRouter()
 
-    const onLogout = () => {
+    const onLogout = async () => {
+        const token = localStorage.getItem("token");
+        const refreshToken = localStorage.getItem("refresh_token");
+        // Revokes both tokens; logging out locally goes ahead regardless.
+        await fetch(`${process.env.NEXT_PUBLIC_BACKEND_URL}/api/logout`, {
+            method: "POST",
+            headers: {
+                "Content-Type": "application/json",
+                ...(token ? { Authorization: `Bearer ${token}` } : {}),
+            },
+            body: JSON.stringify({ refresh_token: refreshToken }),
+        }).catch((error) => console.error("Error logging out:", error));
+
         localStorage.removeItem("token");
+        localStorage.removeItem("refresh_token");
         logout()
         router.push("/")
     }
@@ -1,7 +1,7 @@
 "use client";
 
-import { useEffect } from "react";
-import { useRouter } from "next/navigation";
+import { FormEvent, useEffect, useState } from "react";
+import toast from "react-hot-toast";
 import { useUser } from "../_utils/UserContext";
 
 interface User {
@@ -11,25 +11,99 @@ interface User {
 
 interface ClientDashboardProps {
   user: User;
-  token: string;
 }
 
-export default function ClientDashboard({ user, token }: ClientDashboardProps) {
+export default function ClientDashboard({ user }: ClientDashboardProps) {
   const { setUser } = useUser();
-  const router = useRouter();
+  const [hasToken, setHasToken] = useState(false);
+  const [password, setPassword] = useState("");
+  const [submitting, setSubmitting] = useState(false);
 
   useEffect(() => {
     setUser(user);
-    if (token) {
-      localStorage.setItem("token", token);
+    setHasToken(!!localStorage.getItem("token"));
+  }, [user, setUser]);
+
+  // Logs in to the polling backend, or creates the account on first use.
+  const authenticate = async (action: "login" | "register") => {
+    setSubmitting(true);
+    try {
+      const response = await fetch(
+        `${process.env.NEXT_PUBLIC_BACKEND_URL}/api/${action}`,
+        {
+          method: "POST",
+          headers: { "Content-Type": "application/json" },
+          body: JSON.stringify({
+            user_id: user.userId,
+            name: user.fullName,
+            password,
+          }),
+        }
+      );
+
+      if (!response.ok) {
+        toast.error((await response.text()) || "Authentication failed.");
+        return;
+      }
+
+      const data = await response.json();
+      localStorage.setItem("token", data.token);
+      localStorage.setItem("refresh_token", data.refresh_token);
+      setPassword("");
+      setHasToken(true);
+    } catch (error) {
+      console.error("Error authenticating:", error);
+      toast.error("An error occurred. Please try again.");
+    } finally {
+      setSubmitting(false);
     }
-  }, [user, token, setUser]);
+  };
+
+  const onSubmit = (event: FormEvent) => {
+    event.preventDefault();
+    authenticate("login");
+  };
 
   return (
     <div className="p-6">
       <h2 className="text-2xl font-semibold">
         Welcome to your Dashboard, {user.fullName}!
       </h2>
+
+      {!hasToken && (
+        <form onSubmit={onSubmit} className="mt-6 max-w-sm space-y-4">
+          <p className="text-gray-600">
+            Enter your polling password to continue, or choose one to create
+            your account.
+          </p>
+          <input
+            type="password"
+            value={password}
+            onChange={(e) => setPassword(e.target.value)}
+            placeholder="Password"
+            minLength={8}
+            required
+            className="w-full p-2 border rounded-md"
+          />
+          <div className="flex gap-2">
+            <button
+              type="submit"
+              disabled={submitting}
+              className="px-4 py-2 bg-blue-600 text-white rounded-md"
+            >
+              Log In
+            </button>
+            <button
+              type="button"
+              disabled={submitting || password.length < 8}
+              onClick={() => authenticate("register")}
+              className="px-4 py-2 bg-green-500 text-white rounded-md"
+            >
+              Create Account
+            </button>
+          </div>
+        </form>
+      )}
     </div>
   );
 }
@@ -1,6 +1,6 @@
 "use client";
 
-import { useEffect, useState } from "react";
+import { useCallback, useEffect, useState } from "react";
 import { useRouter } from "next/navigation";
 
 interface Poll {
@@ -13,17 +13,24 @@ interface Poll {
 
 export default function AllPollsPage() {
   const [polls, setPolls] = useState<Poll[]>([]);
+  const [nextCursor, setNextCursor] = useState<string | null>(null);
   const router = useRouter();
 
-  useEffect(() => {
-    const fetchPolls = async () => {
-      const response = await fetch(`${process.env.NEXT_PUBLIC_BACKEND_URL}/api/all_polls_summary`);
-      const data = await response.json();
-      setPolls(data);
-    };
+  // The summary is paged: without a cursor the first page replaces the list,
+  // with one the following page is appended to it.
+  const fetchPolls = useCallback(async (cursor: string | null = null) => {
+    const query = cursor ? `?after=${encodeURIComponent(cursor)}` : "";
+    const response = await fetch(
+      `${process.env.NEXT_PUBLIC_BACKEND_URL}/api/all_polls_summary${query}`
+    );
+    const data: Poll[] = await response.json();
+    setPolls((current) => (cursor ? [...current, ...data] : data));
+    setNextCursor(response.headers.get("X-Next-Cursor"));
+  }, []);
 
+  useEffect(() => {
     fetchPolls();
-  }, []);
+  }, [fetchPolls]);
 
   const formatDate = (dateString: string) =>
     new Date(dateString).toLocaleDateString("en-US", {
@@ -55,6 +62,14 @@ export default function AllPollsPage() {
           </li>
         ))}
       </ul>
+      {nextCursor && (
+        <button
+          onClick={() => fetchPolls(nextCursor)}
+          className="mt-6 px-4 py-2 bg-blue-600 text-white rounded-md"
+        >
+          Load more
+        </button>
+      )}
     </div>
   );
 }
\ No newline at end of file
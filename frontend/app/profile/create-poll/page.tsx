@@ -1,7 +1,6 @@
 "use client";
 import { useState } from "react";
 import { FaTrashAlt } from "react-icons/fa";
-import { useUser } from "../../_utils/UserContext";
 import toast from "react-hot-toast";
 
 export default function CreatePollPage() {
@@ -9,9 +8,6 @@ export default function CreatePollPage() {
   const [options, setOptions] = useState<string[]>(["", ""]);
   const [multipleChoices, setMultipleChoices] = useState(false);
 
-  const { user } = useUser();
-  const user_id = user?.userId;
-  
   const addOption = () => setOptions([...options, ""]);
 
   const removeOption = (index: number) =>
@@ -31,11 +27,18 @@ export default function CreatePollPage() {
         toast.error("Please provide at least two options.");
         return;
       }
+      const token = localStorage.getItem("token");
+      if (!token) {
+        toast.error("Please log in to create a poll.");
+        return;
+      }
       const response = await fetch(`${backend_url}/api/create_polls`, {
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          Authorization: `Bearer ${token}`,
+        },
         body: JSON.stringify({
-          created_by: user_id,
           question,
           options: filteredOptions,
           is_multiple_choice: multipleChoices,
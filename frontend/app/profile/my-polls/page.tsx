@@ -15,10 +15,13 @@ interface Poll {
 
 export default function MyPollsPage() {
   const [polls, setPolls] = useState<Poll[]>([]);
+  const [nextCursor, setNextCursor] = useState<string | null>(null);
   const { user } = useUser();
   const router = useRouter();
 
-  const fetchPolls = useCallback(async () => {
+  // The list is paged: without a cursor the first page replaces the list,
+  // with one the following page is appended to it.
+  const fetchPolls = useCallback(async (cursor: string | null = null) => {
     if (!user) return;
 
     try {
@@ -29,8 +32,9 @@ export default function MyPollsPage() {
         return;
       }
 
+      const query = cursor ? `?after=${encodeURIComponent(cursor)}` : "";
       const response = await fetch(
-        `${process.env.NEXT_PUBLIC_BACKEND_URL}/api/polls/user/${user.userId}`,
+        `${process.env.NEXT_PUBLIC_BACKEND_URL}/api/polls/user/${user.userId}${query}`,
         {
           headers: { Authorization: `Bearer ${token}` },
         }
@@ -38,8 +42,9 @@ export default function MyPollsPage() {
 
       if (!response.ok) throw new Error("Failed to fetch polls.");
 
-      const data = await response.json();
-      setPolls(data);
+      const data: Poll[] = await response.json();
+      setPolls((current) => (cursor ? [...current, ...data] : data));
+      setNextCursor(response.headers.get("X-Next-Cursor"));
     } catch (error) {
       console.error("Error fetching polls:", error);
       toast.error("Failed to load polls. Please try again.");
@@ -180,6 +185,14 @@ export default function MyPollsPage() {
           </li>
         ))}
       </ul>
+      {nextCursor && (
+        <button
+          onClick={() => fetchPolls(nextCursor)}
+          className="mt-6 px-4 py-2 bg-blue-600 text-white rounded-md"
+        >
+          Load more
+        </button>
+      )}
     </div>
   );
 }
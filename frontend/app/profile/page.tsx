@@ -21,20 +21,8 @@ export default async function ProfilePage() {
       throw new Error("Invalid user session");
     }
 
-    const response = await fetch("http://localhost:3030/api/login", {
-      method: "POST",
-      headers: { "Content-Type": "application/json" },
-      body: JSON.stringify({
-        user_id: user.userId,
-        name: user.fullName,
-      }),
-    });
-
-    const data = await response.json();
-
-    if (data.token) {
-      return <ClientDashboard user={user} token={data.token} />;
-    }
+    // The backend account is unlocked with a password on the dashboard.
+    return <ClientDashboard user={user} />;
   } catch (error) {
     console.error("Error during session validation or login:", error);
     redirect("/");